@@ -0,0 +1,296 @@
+//! Binary account dumps, as served by the RPC's `getProgramAccountsBinary`
+//! route: length‑prefixed [`compress`](crate::compress) frames of
+//! fixed‑layout records, so bulk consumers skip JSON and base64 entirely.
+//!
+//! ```text
+//! dump:   magic "FRDUMP01" frame* end
+//! frame:  compressed_len:u32 lz4_frame[compressed_len]   (compressed_len > 0)
+//! end:    0:u32
+//! record: pubkey[32] owner[32] lamports:u64 slot:u64 data_len:u32 data[data_len]
+//! ```
+//!
+//! Integers are little‑endian. A frame holds whole records and stays within
+//! [`MAX_FRAME`] bytes, compressed and decompressed. The end marker tells a
+//! complete dump from one cut short (the server aborts the body if it fails
+//! mid‑way); [`DumpReader`] reports the latter as an error.
+
+use std::io::{self, Read};
+
+pub const MAGIC: &[u8; 8] = b"FRDUMP01";
+
+/// Written after the last frame.
+pub const END: &[u8; 4] = &[0; 4];
+
+/// Largest frame a reader accepts. Writers cut frames around 1 MiB, so even
+/// a frame ending in a 10 MiB account fits.
+pub const MAX_FRAME: usize = 16 << 20;
+
+/// Bytes of a record before its data.
+const RECORD_HEADER_LEN: usize = 32 + 32 + 8 + 8 + 4;
+
+/// One account of a dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DumpRecord {
+    pub pubkey: [u8; 32],
+    pub owner: [u8; 32],
+    pub lamports: u64,
+    /// Slot the account was last written at.
+    pub slot: u64,
+    pub data: Vec<u8>,
+}
+
+/// Encoder side: collects records and cuts them into frames. The caller
+/// writes [`MAGIC`], the frames, then [`END`].
+#[derive(Default)]
+pub struct DumpWriter {
+    records: Vec<u8>,
+}
+
+impl DumpWriter {
+    /// Append a record to the current frame.
+    pub fn push(
+        &mut self,
+        pubkey: &[u8; 32],
+        owner: &[u8; 32],
+        lamports: u64,
+        slot: u64,
+        data: &[u8],
+    ) {
+        self.records.extend_from_slice(pubkey);
+        self.records.extend_from_slice(owner);
+        self.records.extend_from_slice(&lamports.to_le_bytes());
+        self.records.extend_from_slice(&slot.to_le_bytes());
+        self.records.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.records.extend_from_slice(data);
+    }
+
+    /// Uncompressed bytes in the current frame.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Compress the current frame and append it, length‑prefixed, to `out`;
+    /// nothing if no record was pushed since the last frame.
+    pub fn finish_frame(&mut self, out: &mut Vec<u8>) {
+        if self.records.is_empty() {
+            return;
+        }
+        let frame = crate::compress(&self.records);
+        out.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        out.extend_from_slice(&frame);
+        self.records.clear();
+    }
+}
+
+/// Decoder side: reads a dump and yields its records in order.
+pub struct DumpReader<R> {
+    inner: R,
+    /// Decompressed records of the current frame, and how far they are read.
+    frame: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> DumpReader<R> {
+    /// Start reading a dump, checking its magic.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an account dump"));
+        }
+        Ok(Self {
+            inner,
+            frame: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    /// Load the next frame; `false` at the end marker.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        let mut len = [0u8; 4];
+        self.inner.read_exact(&mut len).map_err(truncated)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len == 0 {
+            return Ok(false);
+        }
+        if len > MAX_FRAME {
+            return Err(invalid("frame over the size limit"));
+        }
+        let mut compressed = vec![0u8; len];
+        self.inner.read_exact(&mut compressed).map_err(truncated)?;
+        self.frame.clear();
+        // One byte past the limit tells a frame that fits from one that does
+        // not, without inflating the rest.
+        lz4::Decoder::new(compressed.as_slice())?
+            .take(MAX_FRAME as u64 + 1)
+            .read_to_end(&mut self.frame)?;
+        if self.frame.len() > MAX_FRAME {
+            self.frame.clear();
+            return Err(invalid("frame over the size limit"));
+        }
+        self.pos = 0;
+        Ok(true)
+    }
+
+    fn record(&mut self) -> io::Result<DumpRecord> {
+        let rest = &self.frame[self.pos..];
+        if rest.len() < RECORD_HEADER_LEN {
+            return Err(invalid("record header cut by the frame"));
+        }
+        let data_len = u32::from_le_bytes(rest[80..84].try_into().unwrap()) as usize;
+        let data = rest
+            .get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + data_len)
+            .ok_or_else(|| invalid("record data cut by the frame"))?;
+        let record = DumpRecord {
+            pubkey: rest[0..32].try_into().unwrap(),
+            owner: rest[32..64].try_into().unwrap(),
+            lamports: u64::from_le_bytes(rest[64..72].try_into().unwrap()),
+            slot: u64::from_le_bytes(rest[72..80].try_into().unwrap()),
+            data: data.to_vec(),
+        };
+        self.pos += RECORD_HEADER_LEN + data_len;
+        Ok(record)
+    }
+}
+
+impl<R: Read> Iterator for DumpReader<R> {
+    type Item = io::Result<DumpRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.pos < self.frame.len() {
+                let record = self.record();
+                self.done = record.is_err();
+                return Some(record);
+            }
+            match self.next_frame() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// EOF before the end marker means the dump was cut short.
+fn truncated(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "dump ended without its end marker")
+    } else {
+        e
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(n: u8, data_len: usize) -> DumpRecord {
+        DumpRecord {
+            pubkey: [n; 32],
+            owner: [n.wrapping_add(1); 32],
+            lamports: n as u64 * 1_000,
+            slot: n as u64,
+            data: vec![n; data_len],
+        }
+    }
+
+    /// A dump of `records`, cut into frames of `per_frame` records.
+    fn dump(records: &[DumpRecord], per_frame: usize) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        let mut writer = DumpWriter::default();
+        for chunk in records.chunks(per_frame) {
+            for r in chunk {
+                writer.push(&r.pubkey, &r.owner, r.lamports, r.slot, &r.data);
+            }
+            writer.finish_frame(&mut out);
+        }
+        out.extend_from_slice(END);
+        out
+    }
+
+    fn read(bytes: &[u8]) -> io::Result<Vec<DumpRecord>> {
+        DumpReader::new(bytes)?.collect()
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records: Vec<DumpRecord> = (0..10).map(|n| record(n, n as usize * 7)).collect();
+        assert_eq!(read(&dump(&records, 3)).unwrap(), records);
+        assert_eq!(read(&dump(&[], 3)).unwrap(), []);
+        // An empty frame adds nothing.
+        let mut writer = DumpWriter::default();
+        let mut out = Vec::new();
+        writer.finish_frame(&mut out);
+        assert!(out.is_empty() && writer.is_empty());
+    }
+
+    #[test]
+    fn truncated_dump_is_an_error() {
+        let records: Vec<DumpRecord> = (0..4).map(|n| record(n, 100)).collect();
+        let bytes = dump(&records, 2);
+        // Without the end marker, then cut inside the last frame.
+        for cut in [bytes.len() - END.len(), bytes.len() - END.len() - 3] {
+            let err = read(&bytes[..cut]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+        assert_eq!(
+            read(b"FRDUMP00").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn corrupt_frames_are_errors() {
+        let bytes = dump(&[record(1, 100)], 1);
+        // Garbage in place of the LZ4 frame.
+        let mut garbage = bytes.clone();
+        let frame_len = garbage.len() - MAGIC.len() - 4 - END.len();
+        garbage[MAGIC.len() + 4..][..frame_len].fill(0xa5);
+        assert!(read(&garbage).is_err());
+
+        // A frame holding half a record.
+        let mut out = MAGIC.to_vec();
+        let mut writer = DumpWriter::default();
+        writer.push(&[1; 32], &[2; 32], 3, 4, &[5; 10]);
+        let mut frame = Vec::new();
+        writer.finish_frame(&mut frame);
+        let records = crate::decompress(&frame[4..]);
+        let half = crate::compress(&records[..records.len() - 5]);
+        out.extend_from_slice(&(half.len() as u32).to_le_bytes());
+        out.extend_from_slice(&half);
+        out.extend_from_slice(END);
+        assert_eq!(read(&out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        // A length prefix over the limit is refused before reading it.
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&(MAX_FRAME as u32 + 1).to_le_bytes());
+        assert_eq!(read(&out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // A small frame that inflates past the limit.
+        let bomb = crate::compress(&vec![0; MAX_FRAME + 1]);
+        assert!(bomb.len() < MAX_FRAME);
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&(bomb.len() as u32).to_le_bytes());
+        out.extend_from_slice(&bomb);
+        out.extend_from_slice(END);
+        assert_eq!(read(&out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
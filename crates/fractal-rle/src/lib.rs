@@ -1,6 +1,8 @@
 use std::io::{Cursor, Write};
 use lz4::{Decoder, EncoderBuilder};
 
+pub mod dump;
+
 pub fn compress(data: &[u8]) -> Vec<u8> {
     let mut enc = EncoderBuilder::new().level(1).build(Vec::new()).unwrap();
     enc.write_all(data).unwrap();
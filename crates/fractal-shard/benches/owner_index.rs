@@ -0,0 +1,63 @@
+//! Owner‑index insert throughput with one owner holding 10M accounts (the
+//! SPL Token program on mainnet).
+//!
+//! ```text
+//! cargo bench -p fractal-shard --bench owner_index
+//! ```
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use fractal_shard::owner_index::OwnerIndex;
+use solana_sdk::pubkey::Pubkey;
+
+const ACCOUNTS: usize = 10_000_000;
+
+/// A uniformly random key. `Pubkey::new_unique` counts up from zero, which
+/// would put every key in the first key‑range partition.
+fn random_key() -> Pubkey {
+    Pubkey::new_from_array(rand::random())
+}
+
+fn owner_index(c: &mut Criterion) {
+    let owner = random_key();
+    let keys: Vec<Pubkey> = (0..ACCOUNTS).map(|_| random_key()).collect();
+
+    let mut group = c.benchmark_group("owner_index");
+    group.sample_size(10);
+
+    // Fill an empty index with 10M keys under one owner.
+    group.throughput(Throughput::Elements(ACCOUNTS as u64));
+    group.bench_function("insert_10m_one_owner", |b| {
+        b.iter_batched(
+            OwnerIndex::default,
+            |index| {
+                for key in &keys {
+                    index.insert(owner, *key);
+                }
+                index
+            },
+            BatchSize::PerIteration,
+        )
+    });
+
+    // Steady state: one write into (and out of) an owner already at 10M.
+    let index = OwnerIndex::default();
+    for key in &keys {
+        index.insert(owner, *key);
+    }
+    group.throughput(Throughput::Elements(1));
+    group.sample_size(100);
+    group.bench_function("insert_remove_at_10m", |b| {
+        b.iter_batched(
+            random_key,
+            |key| {
+                index.insert(owner, key);
+                index.remove(&owner, &key);
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, owner_index);
+criterion_main!(benches);
@@ -0,0 +1,166 @@
+//! Memcmp filters maintained as secondary indexes.
+//!
+//! A program can register windows of its account data (`offset`, `length`)
+//! that `getProgramAccounts` clients filter on, such as an Anchor
+//! discriminator at 0..8 or the token account owner at 32..64. Every account
+//! of the program is then listed under the bytes it holds in each window, so
+//! a memcmp on exactly that window is a look‑up instead of a scan.
+
+use crate::owner_index::{KeySet, OwnerIndex};
+use dashmap::DashMap;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::sync::Arc;
+
+/// Key‑range partitions per window value (a discriminator can cover every
+/// account of a program).
+const VALUE_RANGES: usize = 16;
+
+/// Accounts of one program, grouped by the bytes at
+/// `offset..offset + length` of their data.
+pub struct MemcmpIndex {
+    pub offset: usize,
+    pub length: usize,
+    keys: OwnerIndex<Box<[u8]>>,
+}
+
+impl MemcmpIndex {
+    fn new(offset: usize, length: usize) -> Self {
+        Self {
+            offset,
+            length,
+            keys: OwnerIndex::with_ranges(VALUE_RANGES),
+        }
+    }
+
+    /// The window of `data` this index groups by, or `None` if `data` is too
+    /// short to hold it (such accounts are not listed).
+    pub fn value<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        data.get(self.offset..self.offset.checked_add(self.length)?)
+    }
+
+    /// `true` if a memcmp of `bytes` at `offset` is served by this index.
+    pub fn covers(&self, offset: usize, bytes: &[u8]) -> bool {
+        self.offset == offset && self.length == bytes.len()
+    }
+
+    /// List `key` under its window of `data`.
+    pub fn insert(&self, key: Pubkey, data: &[u8]) {
+        if let Some(value) = self.value(data) {
+            self.keys.insert(value.into(), key);
+        }
+    }
+
+    /// Keys listed under `bytes`, in ascending order.
+    pub fn get(&self, bytes: &[u8]) -> Option<Arc<KeySet>> {
+        self.keys.get(&Box::from(bytes))
+    }
+
+    /// Every `(window value, key set)` pair; see [`OwnerIndex::owners`].
+    pub fn values(&self) -> Vec<(Box<[u8]>, Arc<KeySet>)> {
+        self.keys.owners()
+    }
+
+    fn remove(&self, key: &Pubkey, data: &[u8]) {
+        if let Some(value) = self.value(data) {
+            self.keys.remove(&Box::from(value), key);
+        }
+    }
+}
+
+/// Program → its registered [`MemcmpIndex`]es.
+#[derive(Default)]
+pub struct FilterIndexes {
+    programs: DashMap<Pubkey, Vec<Arc<MemcmpIndex>>>,
+}
+
+impl FilterIndexes {
+    /// Register a window of `program`'s data. Returns the new (empty) index,
+    /// or `None` if the window is empty or already registered.
+    pub fn register(
+        &self,
+        program: Pubkey,
+        offset: usize,
+        length: usize,
+    ) -> Option<Arc<MemcmpIndex>> {
+        if length == 0 || offset.checked_add(length).is_none() {
+            return None;
+        }
+        let mut indexes = self.programs.entry(program).or_default();
+        if indexes
+            .iter()
+            .any(|i| i.offset == offset && i.length == length)
+        {
+            return None;
+        }
+        let index = Arc::new(MemcmpIndex::new(offset, length));
+        indexes.push(index.clone());
+        Some(index)
+    }
+
+    /// The index of `program` that serves a memcmp of `bytes` at `offset`.
+    pub fn find(
+        &self,
+        program: &Pubkey,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Option<Arc<MemcmpIndex>> {
+        self.programs
+            .get(program)?
+            .iter()
+            .find(|i| i.covers(offset, bytes))
+            .cloned()
+    }
+
+    /// Every registered index with its program.
+    pub fn all(&self) -> Vec<(Pubkey, Arc<MemcmpIndex>)> {
+        self.programs
+            .iter()
+            .flat_map(|e| {
+                let program = *e.key();
+                e.value()
+                    .iter()
+                    .map(|index| (program, index.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// `(offset, length)` of every window registered for `program`.
+    pub fn windows(&self, program: &Pubkey) -> Vec<(usize, usize)> {
+        self.programs
+            .get(program)
+            .map(|indexes| indexes.iter().map(|i| (i.offset, i.length)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Move `key` from the windows of its previous state `old` to those of
+    /// its current state `new` (`None` when it is created or removed).
+    pub fn reindex(&self, key: Pubkey, old: Option<&Account>, new: Option<&Account>) {
+        let moved = match (old, new) {
+            (Some(old), Some(new)) => old.owner != new.owner,
+            _ => true,
+        };
+        if let Some(old) = old {
+            if let Some(indexes) = self.programs.get(&old.owner) {
+                for index in indexes.iter() {
+                    let changed =
+                        moved || new.and_then(|n| index.value(&n.data)) != index.value(&old.data);
+                    if changed {
+                        index.remove(&key, &old.data);
+                    }
+                }
+            }
+        }
+        if let Some(new) = new {
+            if let Some(indexes) = self.programs.get(&new.owner) {
+                for index in indexes.iter() {
+                    let changed =
+                        moved || old.and_then(|o| index.value(&o.data)) != index.value(&new.data);
+                    if changed {
+                        index.insert(key, &new.data);
+                    }
+                }
+            }
+        }
+    }
+}
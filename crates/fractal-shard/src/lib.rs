@@ -1,23 +1,68 @@
 //! Sharded, lock‑free in‑memory index for Solana accounts.
 //! - Full‑hash sharding.
 //! - Optional Redis backing for a distributed cache (feature `distributed`).
-//! - Token‑owner secondary index for O(1) token‑account look‑ups.
+//! - Token‑owner secondary index for O(1) token‑account look‑ups, plus
+//!   wallet → token accounts and mint → token accounts indexes covering
+//!   both SPL Token and Token‑2022.
+//! - Every entry remembers the slot / write_version it was written at, so
+//!   stale (out‑of‑order) updates never overwrite newer data.
+//! - Commitment levels: the shards hold rooted (finalized) state; writes of
+//!   not‑yet‑rooted slots are kept per slot and promoted once the slot is
+//!   rooted, or dropped if the slot ends up on a dead fork.
+//! - Startup fast path: snapshot accounts only touch the primary shards;
+//!   secondary indexes are built in one pass when the snapshot is complete.
+//! - Closed accounts (zero lamports) are removed from every index instead of
+//!   being cached forever.
+//! - Per‑program memcmp indexes (see [`filter_index`]) for windows of account
+//!   data that `getProgramAccounts` filters commonly match on.
 
-use dashmap::DashMap;
-use solana_sdk::{account::Account, pubkey::Pubkey};
+use dashmap::{
+    mapref::entry::{Entry, OccupiedEntry},
+    DashMap,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    fmt,
     hash::{Hash, Hasher},
-    sync::Arc,
+    ops::{
+        Bound::{Excluded, Unbounded},
+        Deref,
+    },
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
 };
 use std::sync::RwLock;
 
 #[cfg(feature = "distributed")]
 use redis::{AsyncCommands, Client as RedisClient};
 
+pub mod filter_index;
+pub mod owner_index;
+pub mod token;
+pub mod wire;
+
+use {
+    filter_index::FilterIndexes,
+    owner_index::{OwnerIndex, RankedIndex},
+    token::TokenAccount,
+};
+
 const SHARD_BITS: usize = 12; // 4096 shards
 const SHARD_MASK: usize = (1 << SHARD_BITS) - 1;
 
+/// Key‑range partitions per wallet or delegate (most hold a handful of token
+/// accounts).
+const WALLET_RANGES: usize = 1;
+/// Key‑range partitions per mint (popular mints hold millions).
+const MINT_RANGES: usize = 16;
+
+/// Keys copied out of an index per step of a page walk.
+const PAGE_BATCH: usize = 1024;
+
 /// Compute a deterministic shard index from a full Pubkey.
 fn shard_index(key: &Pubkey) -> usize {
     let mut hasher = DefaultHasher::new();
@@ -25,11 +70,244 @@ fn shard_index(key: &Pubkey) -> usize {
     (hasher.finish() as usize) & SHARD_MASK
 }
 
+/// An account plus the position in the Geyser stream it was last written at.
+/// Derefs to the inner `Account` so callers can read `lamports`, `data`, …
+/// directly.
+#[derive(Clone, Debug, Default)]
+pub struct StoredAccount {
+    pub account: Account,
+    pub slot: u64,
+    pub write_version: u64,
+    /// Transaction that last wrote the account, when the source reports it
+    /// (not for snapshot accounts or `ReplicaAccountInfo` V0_0_1).
+    pub txn_signature: Option<Signature>,
+}
+
+impl StoredAccount {
+    pub fn new(account: Account, slot: u64, write_version: u64) -> Self {
+        Self {
+            account,
+            slot,
+            write_version,
+            txn_signature: None,
+        }
+    }
+
+    pub fn with_txn_signature(mut self, txn_signature: Option<Signature>) -> Self {
+        self.txn_signature = txn_signature;
+        self
+    }
+
+    /// `true` if `self` was written strictly before `other` in the stream.
+    /// Slots are compared first; `write_version` breaks ties within a slot.
+    pub fn is_older_than(&self, other: &StoredAccount) -> bool {
+        (self.slot, self.write_version) < (other.slot, other.write_version)
+    }
+
+    /// `true` if the update closes the account. The runtime garbage‑collects
+    /// any account left with zero lamports, whatever its data.
+    pub fn is_closed(&self) -> bool {
+        self.account.lamports == 0
+    }
+}
+
+impl Deref for StoredAccount {
+    type Target = Account;
+
+    fn deref(&self) -> &Account {
+        &self.account
+    }
+}
+
+/// Commitment level a read is served at (mirrors Solana's `commitment`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    #[default]
+    Finalized,
+}
+
+impl Commitment {
+    pub const ALL: [Commitment; 3] = [
+        Commitment::Processed,
+        Commitment::Confirmed,
+        Commitment::Finalized,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Commitment::Processed),
+            "confirmed" => Ok(Commitment::Confirmed),
+            "finalized" => Ok(Commitment::Finalized),
+            other => Err(format!("invalid commitment: {other}")),
+        }
+    }
+}
+
+/// Called with each slot a commitment level advances to.
+type SlotListener = Box<dyn Fn(u64, Commitment) + Send + Sync>;
+
+/// Latest slot per commitment level. Shared (`Arc`) between the ingest side
+/// that advances it and readers such as `getSlot` or slot subscriptions.
+#[derive(Default)]
+pub struct SlotTracker {
+    processed: AtomicU64,
+    confirmed: AtomicU64,
+    finalized: AtomicU64,
+    listeners: RwLock<Vec<SlotListener>>,
+}
+
+impl SlotTracker {
+    /// Advance the slot for `commitment` (and every weaker level). Slots
+    /// never move backwards. Listeners hear of every level that advanced.
+    pub fn update(&self, slot: u64, commitment: Commitment) {
+        for level in Commitment::ALL.into_iter().filter(|level| *level <= commitment) {
+            if self.cell(level).fetch_max(slot, Ordering::Relaxed) < slot {
+                for listener in self.listeners.read().unwrap().iter() {
+                    listener(slot, level);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, commitment: Commitment) -> u64 {
+        self.cell(commitment).load(Ordering::Relaxed)
+    }
+
+    /// Call `listener` on every advance, on the thread that reports it (the
+    /// ingest path), so it must not block.
+    pub fn on_advance(&self, listener: impl Fn(u64, Commitment) + Send + Sync + 'static) {
+        self.listeners.write().unwrap().push(Box::new(listener));
+    }
+
+    fn cell(&self, commitment: Commitment) -> &AtomicU64 {
+        match commitment {
+            Commitment::Processed => &self.processed,
+            Commitment::Confirmed => &self.confirmed,
+            Commitment::Finalized => &self.finalized,
+        }
+    }
+}
+
+impl fmt::Debug for SlotTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlotTracker")
+            .field("processed", &self.processed)
+            .field("confirmed", &self.confirmed)
+            .field("finalized", &self.finalized)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes received for a slot that has not been rooted yet.
+#[derive(Default)]
+struct PendingSlot {
+    confirmed: AtomicBool,
+    /// Parent slot, once reported; links the slot into its fork.
+    parent: OnceLock<u64>,
+    writes: DashMap<Pubkey, Arc<StoredAccount>>,
+}
+
+/// Not‑yet‑rooted writes of one slot, as `(slot, parent, confirmed, writes)`;
+/// see [`ShardedIndex::pending_snapshot`].
+pub type PendingSnapshot = (u64, Option<u64>, bool, Vec<(Pubkey, Arc<StoredAccount>)>);
+
+/// The pending writes a walk over one program sees at one commitment, taken
+/// once with [`ShardedIndex::pending_overlay`] and reused for every page.
+#[derive(Debug, Default)]
+pub struct PendingOverlay {
+    program: Pubkey,
+    /// Keys of the program, or moving into it, with a visible pending write:
+    /// the write if the account is open and owned by the program, `None` if
+    /// it hides a rooted account that moved away or closed.
+    writes: BTreeMap<Pubkey, Option<Arc<StoredAccount>>>,
+}
+
+/// Result of [`ShardedIndex::audit`].
+#[derive(Debug, Default)]
+pub struct IndexAudit {
+    /// Accounts checked in the primary shards.
+    pub accounts: usize,
+    /// Entries the primary shards call for that a secondary index lacks.
+    pub missing: Vec<IndexEntry>,
+    /// Entries of a secondary index that no stored account calls for (the
+    /// account changed or is gone).
+    pub stale: Vec<IndexEntry>,
+}
+
+impl IndexAudit {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// One entry of a secondary index, as checked by [`ShardedIndex::audit`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndexEntry {
+    /// `key` listed under the program that owns it.
+    Owner { owner: Pubkey, key: Pubkey },
+    /// Token account `key` listed under the wallet holding it.
+    Wallet { wallet: Pubkey, key: Pubkey },
+    /// Token account `key` listed under its mint.
+    Mint { mint: Pubkey, key: Pubkey },
+    /// Token account `key` listed under its approved delegate.
+    Delegate { delegate: Pubkey, key: Pubkey },
+    /// Token account `key` ranked by `amount` among the accounts of `mint`.
+    Amount { mint: Pubkey, amount: u64, key: Pubkey },
+    /// `key` listed under the `value` it holds in a memcmp index of
+    /// `program` (see [`filter_index`]).
+    Memcmp {
+        program: Pubkey,
+        offset: usize,
+        value: Box<[u8]>,
+        key: Pubkey,
+    },
+}
+
 /// Primary index (sharded hash map) + secondary token‑owner index.
 pub struct ShardedIndex {
-    shards: Vec<DashMap<Pubkey, Arc<Account>>>,
+    shards: Vec<DashMap<Pubkey, Arc<StoredAccount>>>,
     /// owner → set of pubkeys owned by that program (token fast path)
-    owner_index: DashMap<Pubkey, Arc<RwLock<Vec<Pubkey>>>>,
+    owner_index: OwnerIndex,
+    /// wallet → token accounts it holds (token account `owner` field),
+    /// across SPL Token and Token‑2022
+    wallet_index: OwnerIndex,
+    /// mint → token accounts of that mint
+    mint_index: OwnerIndex,
+    /// delegate → token accounts it may spend from
+    delegate_index: OwnerIndex,
+    /// mint → (token amount, token account), for the largest holders
+    amount_index: RankedIndex,
+    /// program → memcmp windows registered with
+    /// [`register_memcmp_index`](Self::register_memcmp_index)
+    filter_index: FilterIndexes,
+    /// Latest processed / confirmed / rooted slot.
+    slots: Arc<SlotTracker>,
+    /// Highest rooted slot; everything in `shards` is at or below it.
+    rooted_slot: AtomicU64,
+    /// key → (slot, write_version) of the close that removed it from
+    /// `shards`, so a stale write delivered after the close cannot bring the
+    /// account back. Kept until the root after the one that settles the close.
+    tombstones: DashMap<Pubkey, (u64, u64)>,
+    /// slot → writes of that slot, for slots above `rooted_slot`.
+    pending: RwLock<BTreeMap<u64, PendingSlot>>,
+    /// Set once the startup snapshot has been fully loaded.
+    ready: AtomicBool,
+    /// Accounts received through the startup fast path.
+    startup_loaded: AtomicU64,
     #[cfg(feature = "distributed")]
     redis: Option<RedisClient>,
 }
@@ -42,7 +320,18 @@ impl Default for ShardedIndex {
 
         Self {
             shards,
-            owner_index: DashMap::new(),
+            owner_index: OwnerIndex::default(),
+            wallet_index: OwnerIndex::with_ranges(WALLET_RANGES),
+            mint_index: OwnerIndex::with_ranges(MINT_RANGES),
+            delegate_index: OwnerIndex::with_ranges(WALLET_RANGES),
+            amount_index: RankedIndex::default(),
+            filter_index: FilterIndexes::default(),
+            slots: Arc::new(SlotTracker::default()),
+            rooted_slot: AtomicU64::new(0),
+            tombstones: DashMap::new(),
+            pending: RwLock::new(BTreeMap::new()),
+            ready: AtomicBool::new(false),
+            startup_loaded: AtomicU64::new(0),
             #[cfg(feature = "distributed")]
             redis: None,
         }
@@ -62,36 +351,74 @@ impl ShardedIndex {
     /// Insert (or replace) an account. Updates both the primary shard and the
     /// secondary owner index. If Redis is enabled, the account is also stored
     /// there (compressed with LZ4).
-    pub fn insert(&self, key: Pubkey, acc: Account) {
+    ///
+    /// An update older than the stored entry (lower slot, or same slot with a
+    /// lower `write_version`) is dropped and `false` is returned. An update
+    /// that closes the account removes it instead (see [`remove`](Self::remove))
+    /// and leaves a tombstone, so a write older than the close is dropped too.
+    pub fn insert(&self, key: Pubkey, acc: StoredAccount) -> bool {
         // ---------- primary shard ----------
         let idx = shard_index(&key);
         let shard = &self.shards[idx];
+        if acc.is_closed() {
+            match shard.entry(key) {
+                Entry::Occupied(e) => {
+                    if acc.is_older_than(e.get()) {
+                        return false;
+                    }
+                    self.bury(key, &acc);
+                    self.remove_entry(e);
+                }
+                Entry::Vacant(_e) => {
+                    if !self.bury(key, &acc) {
+                        return false;
+                    }
+                }
+            }
+            self.slots.update(acc.slot, Commitment::Processed);
+            return true;
+        }
         let arc_acc = Arc::new(acc.clone());
-        shard.insert(key, arc_acc.clone());
-
-        // ---------- secondary owner index ----------
-        let owner_entry = self
-            .owner_index
-            .entry(acc.owner)
-            .or_insert_with(|| Arc::new(RwLock::new(Vec::new())));
-        {
-            let mut vec = owner_entry.write().unwrap();
-            if !vec.contains(&key) {
-                vec.push(key);
+        // The secondary indexes are updated while the shard entry is still
+        // locked, so concurrent writes to `key` cannot interleave a move
+        // between owners.
+        match shard.entry(key) {
+            Entry::Occupied(mut e) => {
+                if acc.is_older_than(e.get()) {
+                    return false;
+                }
+                let old = e.insert(arc_acc);
+                self.index_secondary(key, Some(&old), &acc);
+            }
+            Entry::Vacant(e) => {
+                if self.is_buried(&key, &acc) {
+                    return false;
+                }
+                let _entry = e.insert(arc_acc);
+                self.index_secondary(key, None, &acc);
             }
         }
+        self.slots.update(acc.slot, Commitment::Processed);
 
         // ---------- optional Redis ----------
         #[cfg(feature = "distributed")]
         if let Some(ref client) = self.redis {
             // Store the compressed account data under the key "acct:<pubkey>"
-            let mut conn = client.get_async_connection();
+            let client = client.clone();
             let key_str = format!("acct:{}", key);
-            let serialized = bincode::serialize(&acc).unwrap();
-            let compressed = crate::fractal_rle::compress(&serialized).unwrap();
+            let serialized =
+                bincode::serialize(&(
+                acc.slot,
+                acc.write_version,
+                &acc.account,
+                acc.txn_signature,
+            ))
+            .unwrap();
+            let compressed = fractal_rle::compress(&serialized);
             // Fire‑and‑forget – we don't block the insert path.
             tokio::spawn(async move {
-                let _: () = conn
+                let _: () = client
+                    .get_multiplexed_async_connection()
                     .await
                     .unwrap()
                     .set_ex(key_str, compressed, 86400) // 1‑day TTL
@@ -99,12 +426,225 @@ impl ShardedIndex {
                     .unwrap();
             });
         }
+
+        true
+    }
+
+    /// Drop `key` from the primary shard and the secondary indexes (and from
+    /// Redis, if enabled). Returns the entry that was removed, if any.
+    pub fn remove(&self, key: &Pubkey) -> Option<Arc<StoredAccount>> {
+        match self.shards[shard_index(key)].entry(*key) {
+            Entry::Occupied(e) => Some(self.remove_entry(e)),
+            Entry::Vacant(_) => None,
+        }
+    }
+
+    /// Record that `acc` closed `key`, unless a later close is already
+    /// recorded (then `false`). Called with the shard entry of `key` locked.
+    fn bury(&self, key: Pubkey, acc: &StoredAccount) -> bool {
+        let version = (acc.slot, acc.write_version);
+        match self.tombstones.entry(key) {
+            Entry::Occupied(mut e) => {
+                if version < *e.get() {
+                    return false;
+                }
+                e.insert(version);
+            }
+            Entry::Vacant(e) => {
+                e.insert(version);
+            }
+        }
+        true
+    }
+
+    /// `true` if `key` was closed after `acc` was written. Otherwise `acc`
+    /// re‑creates the account and its tombstone is dropped. Called with the
+    /// vacant shard entry of `key` locked.
+    fn is_buried(&self, key: &Pubkey, acc: &StoredAccount) -> bool {
+        match self.tombstones.entry(*key) {
+            Entry::Occupied(e) if (acc.slot, acc.write_version) < *e.get() => true,
+            Entry::Occupied(e) => {
+                e.remove();
+                false
+            }
+            Entry::Vacant(_) => false,
+        }
+    }
+
+    /// Remove a locked shard entry, unindexing it before the lock is released.
+    fn remove_entry(
+        &self,
+        e: OccupiedEntry<'_, Pubkey, Arc<StoredAccount>>,
+    ) -> Arc<StoredAccount> {
+        let key = *e.key();
+        self.unindex_secondary(key, e.get());
+        let (_, old) = e.remove_entry();
+
+        #[cfg(feature = "distributed")]
+        if let Some(ref client) = self.redis {
+            let client = client.clone();
+            let key_str = format!("acct:{}", key);
+            // Fire‑and‑forget, like the write in `insert`.
+            tokio::spawn(async move {
+                let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+                let _: () = conn.del(key_str).await.unwrap();
+            });
+        }
+
+        old
+    }
+
+    /// Remove `key` from the secondary indexes it was added to for `acc`.
+    fn unindex_secondary(&self, key: Pubkey, acc: &StoredAccount) {
+        self.owner_index.remove(&acc.owner, &key);
+        self.filter_index.reindex(key, Some(&acc.account), None);
+        if let Some(token) = TokenAccount::unpack(&acc.owner, &acc.data) {
+            self.wallet_index.remove(&token.owner, &key);
+            self.mint_index.remove(&token.mint, &key);
+            if let Some(delegate) = token.delegate {
+                self.delegate_index.remove(&delegate, &key);
+            }
+            self.amount_index.remove(&token.mint, token.amount, &key);
+        }
+    }
+
+    /// Index `key` for its current state `acc`, first dropping any entry
+    /// that only applied to its previous state `old` (owner reassigned, token
+    /// account transferred to another wallet, …).
+    fn index_secondary(&self, key: Pubkey, old: Option<&StoredAccount>, acc: &StoredAccount) {
+        fn reindex(
+            index: &OwnerIndex,
+            key: Pubkey,
+            old: Option<Pubkey>,
+            new: Option<Pubkey>,
+        ) {
+            if let Some(old) = old.filter(|old| Some(*old) != new) {
+                index.remove(&old, &key);
+            }
+            if let Some(new) = new {
+                index.insert(new, key);
+            }
+        }
+
+        reindex(&self.owner_index, key, old.map(|o| o.owner), Some(acc.owner));
+        self.filter_index
+            .reindex(key, old.map(|o| &o.account), Some(&acc.account));
+        let old_token = old.and_then(|o| TokenAccount::unpack(&o.owner, &o.data));
+        let token = TokenAccount::unpack(&acc.owner, &acc.data);
+        reindex(
+            &self.wallet_index,
+            key,
+            old_token.map(|t| t.owner),
+            token.map(|t| t.owner),
+        );
+        reindex(
+            &self.mint_index,
+            key,
+            old_token.map(|t| t.mint),
+            token.map(|t| t.mint),
+        );
+        reindex(
+            &self.delegate_index,
+            key,
+            old_token.and_then(|t| t.delegate),
+            token.and_then(|t| t.delegate),
+        );
+        let ranked = |t: TokenAccount| (t.mint, t.amount);
+        if let Some(old) = old_token.filter(|old| Some(ranked(*old)) != token.map(ranked)) {
+            self.amount_index.remove(&old.mint, old.amount, &key);
+        }
+        if let Some(token) = token {
+            self.amount_index.insert(token.mint, token.amount, key);
+        }
+    }
+
+    /// Startup fast path: store a snapshot account in the primary shards
+    /// only. Secondary indexes are built once by
+    /// [`finish_startup`](Self::finish_startup). After startup this behaves
+    /// like [`insert`](Self::insert).
+    pub fn insert_startup(&self, key: Pubkey, acc: StoredAccount) {
+        if self.is_ready() {
+            self.insert(key, acc);
+            return;
+        }
+        self.slots.update(acc.slot, Commitment::Processed);
+        self.insert_primary(key, acc);
+        self.startup_loaded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// [`insert_startup`](Self::insert_startup) for a batch of accounts, with
+    /// the bookkeeping done once per batch.
+    pub fn insert_startup_batch(&self, batch: Vec<(Pubkey, StoredAccount)>) {
+        if self.is_ready() {
+            for (key, acc) in batch {
+                self.insert(key, acc);
+            }
+            return;
+        }
+        let count = batch.len() as u64;
+        let mut max_slot = 0;
+        for (key, acc) in batch {
+            max_slot = max_slot.max(acc.slot);
+            self.insert_primary(key, acc);
+        }
+        self.slots.update(max_slot, Commitment::Processed);
+        self.startup_loaded.fetch_add(count, Ordering::Relaxed);
     }
 
-    /// Retrieve a copy of the `Arc<Account>` for `key`, if present. If the
-    /// account is missing locally but Redis is enabled we try to fetch it from
-    /// Redis and re‑populate the local shard.
-    pub fn get(&self, key: &Pubkey) -> Option<Arc<Account>> {
+    /// Store in the primary shard only, keeping the newer of two versions.
+    /// A closed account is dropped from the shard and tombstoned rather than
+    /// stored.
+    fn insert_primary(&self, key: Pubkey, acc: StoredAccount) {
+        match self.shards[shard_index(&key)].entry(key) {
+            Entry::Occupied(mut e) => {
+                if acc.is_older_than(e.get()) {
+                    return;
+                }
+                if acc.is_closed() {
+                    self.bury(key, &acc);
+                    e.remove();
+                } else {
+                    e.insert(Arc::new(acc));
+                }
+            }
+            Entry::Vacant(e) => {
+                if acc.is_closed() {
+                    self.bury(key, &acc);
+                } else if !self.is_buried(&key, &acc) {
+                    e.insert(Arc::new(acc));
+                }
+            }
+        }
+    }
+
+    /// End of the startup snapshot: build the secondary indexes from the
+    /// primary shards and mark the index ready. Idempotent.
+    pub fn finish_startup(&self) {
+        if self.is_ready() {
+            return;
+        }
+        for shard in &self.shards {
+            for entry in shard.iter() {
+                self.index_secondary(*entry.key(), None, entry.value());
+            }
+        }
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// `true` once the startup snapshot is loaded and the index can serve.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Number of accounts loaded through the startup fast path so far.
+    pub fn startup_loaded(&self) -> u64 {
+        self.startup_loaded.load(Ordering::Relaxed)
+    }
+
+    /// Retrieve a copy of the `Arc<StoredAccount>` for `key`, if present. If
+    /// the account is missing locally but Redis is enabled we try to fetch it
+    /// from Redis and re‑populate the local shard.
+    pub fn get(&self, key: &Pubkey) -> Option<Arc<StoredAccount>> {
         let idx = shard_index(key);
         let shard = &self.shards[idx];
         if let Some(acc) = shard.get(key) {
@@ -118,16 +658,19 @@ impl ShardedIndex {
             let rt = tokio::runtime::Handle::current();
             let key_str = format!("acct:{}", key);
             let maybe_bytes = rt.block_on(async {
-                let mut conn = client.get_async_connection().await.ok()?;
+                let mut conn = client.get_multiplexed_async_connection().await.ok()?;
                 let data: Option<Vec<u8>> = conn.get(key_str).await.ok()?;
                 Some(data)
             })?;
             if let Some(compressed) = maybe_bytes {
-                if let Ok(serialized) = crate::fractal_rle::decompress(&compressed) {
-                    if let Ok(acc) = bincode::deserialize::<Account>(&serialized) {
-                        self.insert(*key, acc.clone());
-                        return Some(Arc::new(acc));
-                    }
+                let serialized = fractal_rle::decompress(&compressed);
+                if let Ok((slot, write_version, acc, txn_signature)) =
+                    bincode::deserialize::<(u64, u64, Account, Option<Signature>)>(&serialized)
+                {
+                    let stored = StoredAccount::new(acc, slot, write_version)
+                        .with_txn_signature(txn_signature);
+                    self.insert(*key, stored.clone());
+                    return Some(Arc::new(stored));
                 }
             }
         }
@@ -135,17 +678,432 @@ impl ShardedIndex {
         None
     }
 
+    /// Record a write from a slot that may not be rooted yet. It becomes
+    /// visible at `Processed` immediately, at `Confirmed` once
+    /// [`confirm_slot`](Self::confirm_slot) is called for its slot and at
+    /// `Finalized` once the slot is rooted. Writes for slots at or below the
+    /// current root go straight to the shards.
+    pub fn insert_processed(&self, key: Pubkey, acc: StoredAccount) -> bool {
+        let slot = acc.slot;
+        {
+            // `rooted_slot` only moves under the write lock, so checking it
+            // under the read lock cannot race with `root_slot()`.
+            let pending = self.pending.read().unwrap();
+            if slot > self.rooted_slot.load(Ordering::Acquire) {
+                if let Some(p) = pending.get(&slot) {
+                    self.slots.update(slot, Commitment::Processed);
+                    return Self::insert_pending(p, key, acc);
+                }
+            } else {
+                drop(pending);
+                return self.insert(key, acc);
+            }
+        }
+
+        let mut pending = self.pending.write().unwrap();
+        if slot <= self.rooted_slot.load(Ordering::Acquire) {
+            drop(pending);
+            return self.insert(key, acc);
+        }
+        self.slots.update(slot, Commitment::Processed);
+        Self::insert_pending(pending.entry(slot).or_default(), key, acc)
+    }
+
+    fn insert_pending(p: &PendingSlot, key: Pubkey, acc: StoredAccount) -> bool {
+        match p.writes.entry(key) {
+            Entry::Occupied(mut e) => {
+                if acc.is_older_than(e.get()) {
+                    return false;
+                }
+                e.insert(Arc::new(acc));
+            }
+            Entry::Vacant(e) => {
+                e.insert(Arc::new(acc));
+            }
+        }
+        true
+    }
+
+    /// Record that the bank for `slot` (child of `parent`) has been
+    /// processed. Advances the processed slot and remembers the parent, which
+    /// [`root_slot`](Self::root_slot) follows to tell the rooted chain from
+    /// forks; writes are tracked by [`insert_processed`](Self::insert_processed).
+    pub fn process_slot(&self, slot: u64, parent: Option<u64>) {
+        self.slots.update(slot, Commitment::Processed);
+        let Some(parent) = parent else {
+            return;
+        };
+        if slot <= self.rooted_slot.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(p) = self.pending.read().unwrap().get(&slot) {
+            let _ = p.parent.set(parent);
+            return;
+        }
+        let mut pending = self.pending.write().unwrap();
+        if slot > self.rooted_slot.load(Ordering::Acquire) {
+            let _ = pending.entry(slot).or_default().parent.set(parent);
+        }
+    }
+
+    /// Mark `slot` as confirmed so its pending writes are served at
+    /// `Confirmed` commitment.
+    pub fn confirm_slot(&self, slot: u64) {
+        self.slots.update(slot, Commitment::Confirmed);
+        if slot <= self.rooted_slot.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(p) = self.pending.read().unwrap().get(&slot) {
+            p.confirmed.store(true, Ordering::Release);
+            return;
+        }
+        let mut pending = self.pending.write().unwrap();
+        if slot > self.rooted_slot.load(Ordering::Acquire) {
+            pending
+                .entry(slot)
+                .or_default()
+                .confirmed
+                .store(true, Ordering::Release);
+        }
+    }
+
+    /// Root `slot`: the pending writes of `slot` and of its ancestors are
+    /// promoted into the shards, so roots the source skipped (a missed
+    /// notification, a reconnect) lose nothing. Pending slots below `slot` off
+    /// its chain were on forks and are discarded.
+    ///
+    /// Promotion runs under the pending write lock and the new root is only
+    /// published afterwards, so no read sees a write leave the pending set
+    /// before it reached the shards.
+    pub fn root_slot(&self, slot: u64) {
+        {
+            let mut pending = self.pending.write().unwrap();
+            let old_root = self.rooted_slot.load(Ordering::Acquire);
+            if slot <= old_root {
+                return;
+            }
+            // Closes settled by the previous root have outlived any late
+            // write of their slot's ancestors.
+            self.tombstones.retain(|_, (closed, _)| *closed > old_root);
+            let newer = pending.split_off(&(slot + 1));
+            let settled = std::mem::replace(&mut *pending, newer);
+            for p in Self::rooted_chain(settled, slot, old_root) {
+                for (key, acc) in p.writes {
+                    self.insert(key, Arc::try_unwrap(acc).unwrap_or_else(|a| (*a).clone()));
+                }
+            }
+            self.rooted_slot.store(slot, Ordering::Release);
+        }
+        self.slots.update(slot, Commitment::Finalized);
+    }
+
+    /// The `settled` pending slots (all above `old_root`, at or below `root`)
+    /// that `root` makes final, oldest first: `root` and its ancestors,
+    /// followed through the parents recorded by
+    /// [`process_slot`](Self::process_slot). Where the chain breaks on a slot
+    /// whose parent was never reported, the slots below the break are kept if
+    /// confirmed (a confirmed slot is all but certain to be rooted) and
+    /// dropped otherwise.
+    fn rooted_chain(
+        mut settled: BTreeMap<u64, PendingSlot>,
+        root: u64,
+        old_root: u64,
+    ) -> Vec<PendingSlot> {
+        let mut chain = Vec::new();
+        let mut next = Some(root);
+        while let Some(slot) = next.filter(|slot| *slot > old_root) {
+            let Some(p) = settled.remove(&slot) else {
+                break;
+            };
+            next = p.parent.get().copied();
+            chain.push(p);
+            if next.is_none() {
+                next = Some(slot);
+                break;
+            }
+        }
+        // `next` is where the walk stopped: at or below `old_root` once the
+        // chain is complete, above it if a parent is unknown.
+        if let Some(broken) = next.filter(|slot| *slot > old_root) {
+            settled.split_off(&broken);
+            chain.extend(
+                settled
+                    .into_values()
+                    .rev()
+                    .filter(|p| p.confirmed.load(Ordering::Acquire)),
+            );
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Newest pending write for `key` visible at `commitment`. `None` means
+    /// the rooted entry (if any) is current; a closed write means the account
+    /// no longer exists at that commitment.
+    fn get_pending(&self, key: &Pubkey, commitment: Commitment) -> Option<Arc<StoredAccount>> {
+        if commitment == Commitment::Finalized {
+            return None;
+        }
+        let pending = self.pending.read().unwrap();
+        pending
+            .values()
+            .rev()
+            .filter(|p| {
+                commitment == Commitment::Processed || p.confirmed.load(Ordering::Acquire)
+            })
+            .find_map(|p| p.writes.get(key).map(|acc| acc.clone()))
+    }
+
+    /// [`get`](Self::get) at the given commitment level.
+    pub fn get_with_commitment(
+        &self,
+        key: &Pubkey,
+        commitment: Commitment,
+    ) -> Option<Arc<StoredAccount>> {
+        match self.get_pending(key, commitment) {
+            Some(acc) if acc.is_closed() => None,
+            Some(acc) => Some(acc),
+            None => self.get(key),
+        }
+    }
+
+    /// [`get_program_accounts`](Self::get_program_accounts) at the given
+    /// commitment level: the rooted result overlaid with the newest visible
+    /// pending write of every key (which may move an account in or out of
+    /// `program`).
+    pub fn get_program_accounts_with_commitment(
+        &self,
+        program: &Pubkey,
+        commitment: Commitment,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        self.overlay_pending(
+            || self.get_program_accounts(program),
+            commitment,
+            |acc| acc.owner == *program,
+        )
+    }
+
+    /// Overlay the `rooted` accounts of a query with the newest pending write
+    /// visible at `commitment` of every key, keeping the writes that still
+    /// satisfy `matches` (or now do).
+    ///
+    /// The overlay is taken before the rooted query runs: a slot rooted in
+    /// between then shows up in both, where the pending write wins, rather
+    /// than in neither.
+    fn overlay_pending(
+        &self,
+        rooted: impl FnOnce() -> Vec<(Pubkey, Arc<StoredAccount>)>,
+        commitment: Commitment,
+        matches: impl Fn(&StoredAccount) -> bool,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        let overlay = self.pending_writes(commitment);
+        let mut accounts = rooted();
+        if overlay.is_empty() {
+            return accounts;
+        }
+
+        accounts.retain(|(k, _)| !overlay.contains_key(k));
+        accounts.extend(
+            overlay
+                .into_iter()
+                .filter(|(_, acc)| !acc.is_closed() && matches(acc)),
+        );
+        accounts
+    }
+
+    /// The newest pending write of every key visible at `commitment`,
+    /// closing writes included (none at finalized).
+    fn pending_writes(&self, commitment: Commitment) -> HashMap<Pubkey, Arc<StoredAccount>> {
+        let mut writes = HashMap::new();
+        if commitment == Commitment::Finalized {
+            return writes;
+        }
+        // Oldest slot first so newer slots overwrite older entries.
+        let pending = self.pending.read().unwrap();
+        for p in pending.values().filter(|p| {
+            commitment == Commitment::Processed || p.confirmed.load(Ordering::Acquire)
+        }) {
+            for entry in p.writes.iter() {
+                writes.insert(*entry.key(), entry.value().clone());
+            }
+        }
+        writes
+    }
+
+    /// Snapshot the pending writes a walk over `program` sees at
+    /// `commitment`. Only writes to accounts of the program, or rooted
+    /// in it, are kept.
+    pub fn pending_overlay(&self, program: &Pubkey, commitment: Commitment) -> PendingOverlay {
+        let mut overlay = PendingOverlay {
+            program: *program,
+            writes: BTreeMap::new(),
+        };
+        if commitment == Commitment::Finalized {
+            return overlay;
+        }
+        let rooted = self.owner_index.get(program);
+        // Oldest slot first so newer slots overwrite older entries.
+        let pending = self.pending.read().unwrap();
+        for p in pending.values().filter(|p| {
+            commitment == Commitment::Processed || p.confirmed.load(Ordering::Acquire)
+        }) {
+            for entry in p.writes.iter() {
+                let (key, acc) = entry.pair();
+                if acc.owner == *program && !acc.is_closed() {
+                    overlay.writes.insert(*key, Some(acc.clone()));
+                } else if rooted.as_ref().is_some_and(|set| set.contains(key)) {
+                    overlay.writes.insert(*key, None);
+                } else {
+                    overlay.writes.remove(key);
+                }
+            }
+        }
+        overlay
+    }
+
+    /// One page of a walk over the accounts of `program` as `overlay` sees
+    /// them: up to `limit` accounts with a key greater than `after` that
+    /// satisfy `matches`, in ascending key order.
+    ///
+    /// Passing the last key of each page as the next `after` returns every
+    /// account that exists for the whole walk exactly once, however writes
+    /// interleave with it: each page only looks past the previous one, and
+    /// every key up to the last one returned has been checked. Accounts
+    /// created or closed during the walk show up depending on where their
+    /// key falls. `memcmp` names a window the filters match on; if an index
+    /// is registered for it (see
+    /// [`register_memcmp_index`](Self::register_memcmp_index)) the walk runs
+    /// over that index instead of the whole program.
+    ///
+    /// Pages are served from the secondary indexes, so they are empty until
+    /// the startup snapshot has been indexed: check
+    /// [`is_ready`](Self::is_ready) before starting a walk.
+    pub fn get_program_accounts_page(
+        &self,
+        program: &Pubkey,
+        memcmp: Option<(usize, &[u8])>,
+        overlay: &PendingOverlay,
+        after: Option<&Pubkey>,
+        limit: usize,
+        matches: impl Fn(&StoredAccount) -> bool,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        debug_assert_eq!(overlay.program, *program);
+        let in_page = |acc: &StoredAccount| acc.owner == *program && matches(acc);
+        let index = memcmp.and_then(|(offset, bytes)| {
+            Some((self.filter_index.find(program, offset, bytes)?, bytes))
+        });
+        let keys_after = |after: Option<&Pubkey>| match &index {
+            Some((index, bytes)) => index
+                .get(bytes)
+                .map(|set| set.keys_after(after, PAGE_BATCH))
+                .unwrap_or_default(),
+            None => self.owned_keys_after(program, after, PAGE_BATCH),
+        };
+
+        // Keys with a visible pending write: that write is their state at
+        // the walk's commitment, whether or not the rooted index lists them.
+        // Past the first `limit` matches they cannot make this page.
+        let pending = match after {
+            Some(after) => overlay.writes.range((Excluded(*after), Unbounded)),
+            None => overlay.writes.range(..),
+        };
+        let mut page: BTreeMap<_, _> = pending
+            .filter_map(|(key, acc)| Some((*key, acc.clone()?)))
+            .filter(|(_, acc)| in_page(acc))
+            .take(limit)
+            .collect();
+
+        // Rooted accounts in key order, until `limit` matches lie at or below
+        // the walk position; anything past it belongs to later pages.
+        let mut walked = after.copied();
+        loop {
+            let keys = keys_after(walked.as_ref());
+            let Some(last) = keys.last().copied() else {
+                break;
+            };
+            for key in keys {
+                if overlay.writes.contains_key(&key) {
+                    continue;
+                }
+                if let Some(acc) = self.get(&key) {
+                    if in_page(&acc) {
+                        page.insert(key, acc);
+                    }
+                }
+            }
+            walked = Some(last);
+            if page.range(..=last).count() >= limit {
+                break;
+            }
+        }
+        page.into_iter().take(limit).collect()
+    }
+
+    /// Accounts of `program` whose data holds `bytes` at `offset`, at
+    /// `commitment`, served from a memcmp index. `None` if no index registered
+    /// for `program` covers exactly that window; the caller has to scan.
+    pub fn get_program_accounts_by_memcmp(
+        &self,
+        program: &Pubkey,
+        offset: usize,
+        bytes: &[u8],
+        commitment: Commitment,
+    ) -> Option<Vec<(Pubkey, Arc<StoredAccount>)>> {
+        let index = self.filter_index.find(program, offset, bytes)?;
+        let matches =
+            |acc: &StoredAccount| acc.owner == *program && index.value(&acc.data) == Some(bytes);
+        // Keys are copied out before any shard is read, as in
+        // `get_program_accounts`.
+        let rooted = || {
+            let keys = index.get(bytes).map(|set| set.keys()).unwrap_or_default();
+            keys.iter()
+                .filter_map(|pk| self.get(pk).map(|acc| (*pk, acc)))
+                .filter(|(_, acc)| matches(acc))
+                .collect()
+        };
+        Some(self.overlay_pending(rooted, commitment, matches))
+    }
+
+    /// Maintain an index of `program`'s accounts by the `length` bytes at
+    /// `offset` of their data, which
+    /// [`get_program_accounts_by_memcmp`](Self::get_program_accounts_by_memcmp)
+    /// serves memcmp filters on that window from. Accounts already stored are
+    /// indexed before this returns. Registering a window twice (or an empty
+    /// one) does nothing.
+    pub fn register_memcmp_index(&self, program: Pubkey, offset: usize, length: usize) {
+        let Some(index) = self.filter_index.register(program, offset, length) else {
+            return;
+        };
+        // Writes from here on maintain the new index. Each stored account is
+        // listed while its shard entry is locked, so a concurrent write cannot
+        // be overtaken by the older data it replaced.
+        for (key, _) in self.get_program_accounts(&program) {
+            if let Some(acc) = self.shards[shard_index(&key)].get(&key) {
+                if acc.owner == program {
+                    index.insert(key, &acc.data);
+                }
+            }
+        }
+    }
+
+    /// `(offset, length)` of the memcmp windows indexed for `program`.
+    pub fn memcmp_indexes(&self, program: &Pubkey) -> Vec<(usize, usize)> {
+        self.filter_index.windows(program)
+    }
+
     /// Return **all** accounts owned by `program`. This uses the secondary
     /// owner index for SPL‑Token‑type queries (fast) and falls back to a full
     /// scan for any other program.
-    pub fn get_program_accounts(&self, program: &Pubkey) -> Vec<(Pubkey, Arc<Account>)> {
+    pub fn get_program_accounts(&self, program: &Pubkey) -> Vec<(Pubkey, Arc<StoredAccount>)> {
         // Fast path: if the program is the SPL Token program we can use the
-        // owner_index directly.
-        if let Some(owner_vec) = self.owner_index.get(program) {
-            let vec = owner_vec.read().unwrap();
-            return vec
+        // owner_index directly. Results come back sorted by pubkey.
+        // The keys are copied out first: `insert` locks a shard before the
+        // owner index, so shards must not be read under an owner‑index lock.
+        if let Some(keys) = self.owned_keys(program) {
+            return keys
                 .iter()
                 .filter_map(|pk| self.get(pk).map(|acc| (*pk, acc)))
+                .filter(|(_, acc)| acc.owner == *program)
                 .collect();
         }
 
@@ -161,37 +1119,662 @@ impl ShardedIndex {
         out
     }
 
-    /// Token‑specific helper – return the *largest* N token accounts for a
-    /// given mint (owner = token program, data layout = SPL Token Account).
-    pub fn get_largest_token_accounts(
+    /// Keys the owner index lists under `owner`, in ascending order, or
+    /// `None` if it lists nothing (e.g. before the startup snapshot is
+    /// indexed). The accounts themselves may have changed since.
+    pub fn owned_keys(&self, owner: &Pubkey) -> Option<Vec<Pubkey>> {
+        self.owner_index.get(owner).map(|set| set.keys())
+    }
+
+    /// Up to `limit` keys listed under `owner` that sort after `after`, for
+    /// cursor pagination that stays stable while accounts come and go.
+    pub fn owned_keys_after(
         &self,
-        mint: &Pubkey,
+        owner: &Pubkey,
+        after: Option<&Pubkey>,
         limit: usize,
-    ) -> Vec<(Pubkey, Arc<Account>)> {
-        // The SPL‑Token program is the owner for all token accounts.
-        let token_program = Pubkey::from_str(
-            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-        )
-        .unwrap();
+    ) -> Vec<Pubkey> {
+        self.owner_index
+            .get(owner)
+            .map(|set| set.keys_after(after, limit))
+            .unwrap_or_default()
+    }
 
-        // Get all token accounts (fast via owner_index)
-        let all_token_accounts = self.get_program_accounts(&token_program);
-        let mut filtered: Vec<(Pubkey, Arc<Account>)> = all_token_accounts
+    /// Token accounts held by `wallet` (the token account `owner` field) at
+    /// `commitment`, sorted by pubkey.
+    pub fn get_token_accounts_by_owner(
+        &self,
+        wallet: &Pubkey,
+        commitment: Commitment,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        self.token_accounts(&self.wallet_index, wallet, commitment, |t| {
+            t.owner == *wallet
+        })
+    }
+
+    /// Token accounts that `delegate` has been approved to spend from at
+    /// `commitment`, sorted by pubkey.
+    pub fn get_token_accounts_by_delegate(
+        &self,
+        delegate: &Pubkey,
+        commitment: Commitment,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        self.token_accounts(&self.delegate_index, delegate, commitment, |t| {
+            t.delegate == Some(*delegate)
+        })
+    }
+
+    /// Token accounts of `mint` at `commitment`, sorted by pubkey.
+    pub fn get_token_accounts_by_mint(
+        &self,
+        mint: &Pubkey,
+        commitment: Commitment,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        self.token_accounts(&self.mint_index, mint, commitment, |t| t.mint == *mint)
+    }
+
+    /// Token accounts of `mint` held by `wallet` at `commitment`, sorted by
+    /// pubkey. Walks whichever of the wallet's and the mint's lists is
+    /// shorter: a wallet usually holds a few accounts, but market makers hold
+    /// thousands while a new mint has a handful.
+    pub fn get_token_accounts_by_owner_and_mint(
+        &self,
+        wallet: &Pubkey,
+        mint: &Pubkey,
+        commitment: Commitment,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        let listed = |index: &OwnerIndex, by: &Pubkey| index.get(by).map_or(0, |set| set.len());
+        let (mut accounts, keep): (_, &dyn Fn(&TokenAccount) -> bool) =
+            if listed(&self.mint_index, mint) < listed(&self.wallet_index, wallet) {
+                (
+                    self.get_token_accounts_by_mint(mint, commitment),
+                    &|t| t.owner == *wallet,
+                )
+            } else {
+                (
+                    self.get_token_accounts_by_owner(wallet, commitment),
+                    &|t| t.mint == *mint,
+                )
+            };
+        accounts.retain(|(_, acc)| {
+            TokenAccount::unpack(&acc.owner, &acc.data).is_some_and(|t| keep(&t))
+        });
+        accounts
+    }
+
+    /// Resolve the keys `index` lists under `by`, dropping any whose account
+    /// changed since the keys were copied out, and overlay the pending
+    /// writes visible at `commitment` (the secondary indexes only follow
+    /// rooted state).
+    fn token_accounts(
+        &self,
+        index: &OwnerIndex,
+        by: &Pubkey,
+        commitment: Commitment,
+        still_matches: impl Fn(&TokenAccount) -> bool,
+    ) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        let matches = |acc: &StoredAccount| {
+            TokenAccount::unpack(&acc.owner, &acc.data).is_some_and(|t| still_matches(&t))
+        };
+        let rooted = || {
+            let keys = index.get(by).map(|set| set.keys()).unwrap_or_default();
+            keys.iter()
+                .filter_map(|pk| self.get(pk).map(|acc| (*pk, acc)))
+                .filter(|(_, acc)| matches(acc))
+                .collect()
+        };
+        let mut accounts = self.overlay_pending(rooted, commitment, matches);
+        accounts.sort_unstable_by_key(|(key, _)| *key);
+        accounts
+    }
+
+    /// Token‑specific helper – return the *largest* N token accounts for a
+    /// given mint (SPL Token or Token‑2022) with their token amounts, largest
+    /// first. Served from the per‑mint ranking, so it never scans the mint.
+    pub fn get_largest_token_accounts(&self, mint: &Pubkey, limit: usize) -> Vec<(Pubkey, u64)> {
+        self.amount_index
+            .top(mint, limit)
             .into_iter()
-            .filter(|(_, acc)| {
-                // Token account data layout: first 32 bytes = mint
-                acc.data.len() >= 32 && &acc.data[0..32] == mint.as_ref()
-            })
+            .map(|(amount, key)| (key, amount))
+            .collect()
+    }
+
+    /// Compare every secondary index (owner, wallet, mint, delegate,
+    /// amount ranking and memcmp) against the primary shards and report
+    /// each divergence. This walks the whole index, so it is meant for
+    /// audits and debugging rather than the request path; writes racing with
+    /// the audit may show up as transient divergences. Before the startup
+    /// snapshot is complete the secondary indexes are not built yet and
+    /// everything is reported as missing.
+    pub fn audit(&self) -> IndexAudit {
+        // Never hold a secondary‑index lock while locking a shard: `insert`
+        // takes them in the opposite order.
+        let mut indexed = HashSet::new();
+        let keyed = |index: &OwnerIndex, entry: fn(Pubkey, Pubkey) -> IndexEntry| {
+            index
+                .owners()
+                .into_iter()
+                .flat_map(move |(group, set)| {
+                    set.keys().into_iter().map(move |key| entry(group, key))
+                })
+                .collect::<Vec<_>>()
+        };
+        indexed.extend(keyed(&self.owner_index, |owner, key| {
+            IndexEntry::Owner { owner, key }
+        }));
+        indexed.extend(keyed(&self.wallet_index, |wallet, key| {
+            IndexEntry::Wallet { wallet, key }
+        }));
+        indexed.extend(keyed(&self.mint_index, |mint, key| {
+            IndexEntry::Mint { mint, key }
+        }));
+        indexed.extend(keyed(&self.delegate_index, |delegate, key| {
+            IndexEntry::Delegate { delegate, key }
+        }));
+        indexed.extend(
+            self.amount_index
+                .entries()
+                .into_iter()
+                .map(|(mint, amount, key)| IndexEntry::Amount { mint, amount, key }),
+        );
+        let mut memcmp: HashMap<Pubkey, Vec<_>> = HashMap::new();
+        for (program, index) in self.filter_index.all() {
+            for (value, set) in index.values() {
+                indexed.extend(set.keys().into_iter().map(|key| IndexEntry::Memcmp {
+                    program,
+                    offset: index.offset,
+                    value: value.clone(),
+                    key,
+                }));
+            }
+            memcmp.entry(program).or_default().push(index);
+        }
+
+        let mut report = IndexAudit::default();
+        for shard in &self.shards {
+            for entry in shard.iter() {
+                report.accounts += 1;
+                let (key, acc) = (*entry.key(), entry.value());
+                let mut expected = vec![IndexEntry::Owner {
+                    owner: acc.owner,
+                    key,
+                }];
+                if let Some(token) = TokenAccount::unpack(&acc.owner, &acc.data) {
+                    expected.push(IndexEntry::Wallet {
+                        wallet: token.owner,
+                        key,
+                    });
+                    expected.push(IndexEntry::Mint {
+                        mint: token.mint,
+                        key,
+                    });
+                    if let Some(delegate) = token.delegate {
+                        expected.push(IndexEntry::Delegate { delegate, key });
+                    }
+                    expected.push(IndexEntry::Amount {
+                        mint: token.mint,
+                        amount: token.amount,
+                        key,
+                    });
+                }
+                for index in memcmp.get(&acc.owner).into_iter().flatten() {
+                    if let Some(value) = index.value(&acc.data) {
+                        expected.push(IndexEntry::Memcmp {
+                            program: acc.owner,
+                            offset: index.offset,
+                            value: value.into(),
+                            key,
+                        });
+                    }
+                }
+                for entry in expected {
+                    if !indexed.remove(&entry) {
+                        report.missing.push(entry);
+                    }
+                }
+            }
+        }
+        report.stale = indexed.into_iter().collect();
+        report
+    }
+
+    /// Number of primary shards; valid arguments to [`shard_snapshot`](Self::shard_snapshot).
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Copy of the rooted entries of one shard. Iterating shard by shard keeps
+    /// each shard lock short while replaying or exporting the whole index.
+    pub fn shard_snapshot(&self, shard: usize) -> Vec<(Pubkey, Arc<StoredAccount>)> {
+        self.shards[shard]
+            .iter()
+            .map(|e| (*e.key(), e.value().clone()))
+            .collect()
+    }
+
+    /// Drop every rooted entry of primary shard `shard` whose key is not in
+    /// `keep`, and return how many were dropped. Used to bring a replica in
+    /// line with a replayed shard: accounts closed while it was disconnected
+    /// are never replayed, so they would otherwise be served forever.
+    pub fn retain_shard(&self, shard: usize, keep: &HashSet<Pubkey>) -> usize {
+        let Some(entries) = self.shards.get(shard) else {
+            return 0;
+        };
+        // Keys are collected first: removing while iterating would deadlock
+        // on the shard lock.
+        let gone: Vec<Pubkey> = entries
+            .iter()
+            .map(|e| *e.key())
+            .filter(|key| !keep.contains(key))
             .collect();
+        let mut dropped = 0;
+        for key in gone {
+            if let Entry::Occupied(e) = entries.entry(key) {
+                // Before startup the secondary indexes are not built yet.
+                if self.is_ready() {
+                    self.remove_entry(e);
+                } else {
+                    e.remove();
+                }
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
+    /// Drop every pending write. Used when the source is about to replay its
+    /// pending slots in full: writes held from before may belong to slots it
+    /// has rooted or abandoned since, and rooting them would resurrect stale
+    /// state.
+    pub fn clear_pending(&self) {
+        self.pending.write().unwrap().clear();
+    }
 
-        // Sort descending by lamports (largest first)
-        filtered.sort_unstable_by_key(|(_, acc)| std::cmp::Reverse(acc.lamports));
-        filtered.truncate(limit);
-        filtered
+    /// Copy of the not‑yet‑rooted writes, oldest slot first, as
+    /// `(slot, parent, confirmed, writes)`.
+    pub fn pending_snapshot(&self) -> Vec<PendingSnapshot> {
+        let pending = self.pending.read().unwrap();
+        pending
+            .iter()
+            .map(|(slot, p)| {
+                let writes = p
+                    .writes
+                    .iter()
+                    .map(|e| (*e.key(), e.value().clone()))
+                    .collect();
+                (
+                    *slot,
+                    p.parent.get().copied(),
+                    p.confirmed.load(Ordering::Acquire),
+                    writes,
+                )
+            })
+            .collect()
     }
 
     /// Number of accounts currently cached (used for health checks and metrics)
     pub fn len(&self) -> usize {
         self.shards.iter().map(|s| s.len()).sum()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Highest processed slot (`context.slot` in RPC responses).
+    pub fn slot(&self) -> u64 {
+        self.slots.get(Commitment::Processed)
+    }
+
+    /// Slot a read at `commitment` reflects.
+    pub fn slot_with_commitment(&self, commitment: Commitment) -> u64 {
+        self.slots.get(commitment)
+    }
+
+    /// Shared handle on the latest slot per commitment level.
+    pub fn slots(&self) -> Arc<SlotTracker> {
+        self.slots.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uniformly random, unlike `Pubkey::new_unique`, whose leading byte is
+    /// always zero, so keys spread over every key‑range partition.
+    fn random_key() -> Pubkey {
+        Pubkey::new_from_array(rand::random())
+    }
+
+    fn account(owner: Pubkey, lamports: u64, slot: u64) -> StoredAccount {
+        let account = Account {
+            lamports,
+            owner,
+            ..Account::default()
+        };
+        StoredAccount::new(account, slot, 0)
+    }
+
+    fn lamports(index: &ShardedIndex, key: &Pubkey, commitment: Commitment) -> Option<u64> {
+        index.get_with_commitment(key, commitment).map(|acc| acc.lamports)
+    }
+
+    /// An initialized SPL Token account of `mint` held by `wallet`.
+    fn token_account(mint: &Pubkey, wallet: &Pubkey, amount: u64, slot: u64) -> StoredAccount {
+        let mut data = vec![0; token::ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(wallet.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1;
+        let mut acc = account(token::TOKEN_PROGRAM_ID, 1, slot);
+        acc.account.data = data;
+        acc
+    }
+
+    /// Index rooted at `root` with one rooted account, ready to serve.
+    fn rooted_index(root: u64) -> (ShardedIndex, Pubkey, Pubkey) {
+        let index = ShardedIndex::default();
+        let (key, owner) = (random_key(), random_key());
+        index.insert(key, account(owner, 1, root));
+        index.finish_startup();
+        index.root_slot(root);
+        (index, key, owner)
+    }
+
+    #[test]
+    fn pending_write_moves_through_commitment_levels() {
+        let (index, key, owner) = rooted_index(10);
+        index.process_slot(11, Some(10));
+        index.insert_processed(key, account(owner, 2, 11));
+
+        assert_eq!(lamports(&index, &key, Commitment::Processed), Some(2));
+        assert_eq!(lamports(&index, &key, Commitment::Confirmed), Some(1));
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(1));
+
+        index.confirm_slot(11);
+        assert_eq!(lamports(&index, &key, Commitment::Confirmed), Some(2));
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(1));
+
+        index.root_slot(11);
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(2));
+        assert!(index.pending_snapshot().is_empty());
+        assert_eq!(index.slot_with_commitment(Commitment::Finalized), 11);
+    }
+
+    #[test]
+    fn rooting_promotes_ancestors_whose_root_was_missed() {
+        let (index, key, owner) = rooted_index(10);
+        let other = random_key();
+        index.process_slot(11, Some(10));
+        index.insert_processed(key, account(owner, 2, 11));
+        index.process_slot(12, Some(11));
+        index.insert_processed(other, account(owner, 5, 12));
+
+        // No root notification for 11.
+        index.root_slot(12);
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(2));
+        assert_eq!(lamports(&index, &other, Commitment::Finalized), Some(5));
+        assert!(index.pending_snapshot().is_empty());
+    }
+
+    #[test]
+    fn rooting_discards_forks() {
+        let (index, key, owner) = rooted_index(10);
+        let forked = random_key();
+        index.process_slot(11, Some(10));
+        index.insert_processed(forked, account(owner, 7, 11));
+        index.insert_processed(key, account(owner, 3, 11));
+        index.process_slot(12, Some(10));
+        index.process_slot(13, Some(12));
+        index.insert_processed(key, account(owner, 4, 13));
+
+        index.root_slot(13);
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(4));
+        assert_eq!(lamports(&index, &forked, Commitment::Finalized), None);
+        assert_eq!(lamports(&index, &forked, Commitment::Processed), None);
+    }
+
+    #[test]
+    fn unknown_parent_keeps_only_confirmed_slots_below_the_break() {
+        let (index, _, owner) = rooted_index(10);
+        let (confirmed, unconfirmed) = (random_key(), random_key());
+        // Writes for 11 and 12 arrive without any slot notification.
+        index.insert_processed(confirmed, account(owner, 1, 11));
+        index.confirm_slot(11);
+        index.insert_processed(unconfirmed, account(owner, 1, 12));
+        index.process_slot(14, None);
+
+        index.root_slot(14);
+        assert!(index.get(&confirmed).is_some());
+        assert!(index.get(&unconfirmed).is_none());
+    }
+
+    #[test]
+    fn pending_close_hides_rooted_account() {
+        let (index, key, owner) = rooted_index(10);
+        index.process_slot(11, Some(10));
+        index.insert_processed(key, account(owner, 0, 11));
+
+        assert_eq!(lamports(&index, &key, Commitment::Processed), None);
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(1));
+        assert!(index
+            .get_program_accounts_with_commitment(&owner, Commitment::Processed)
+            .is_empty());
+        assert_eq!(
+            index
+                .get_program_accounts_with_commitment(&owner, Commitment::Finalized)
+                .len(),
+            1
+        );
+
+        index.root_slot(11);
+        assert!(index.get(&key).is_none());
+    }
+
+    #[test]
+    fn stale_write_after_close_does_not_resurrect() {
+        let (index, key, owner) = rooted_index(10);
+        assert!(index.insert(key, account(owner, 0, 12)));
+        assert!(!index.insert(key, account(owner, 5, 11)));
+        assert!(index.get(&key).is_none());
+        assert!(index.get_program_accounts(&owner).is_empty());
+
+        // A close of an account this index never held is remembered too.
+        let unseen = random_key();
+        assert!(index.insert(unseen, account(owner, 0, 12)));
+        assert!(!index.insert(unseen, account(owner, 5, 11)));
+        assert!(index.get(&unseen).is_none());
+
+        // A later write re-creates the account.
+        assert!(index.insert(key, account(owner, 6, 13)));
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(6));
+    }
+
+    #[test]
+    fn late_write_below_a_rooted_close_is_dropped() {
+        let (index, key, owner) = rooted_index(10);
+        index.process_slot(12, Some(10));
+        index.insert_processed(key, account(owner, 0, 12));
+        index.root_slot(12);
+        assert!(index.get(&key).is_none());
+
+        // Delivered after its descendant's close was rooted.
+        assert!(!index.insert_processed(key, account(owner, 5, 11)));
+        assert_eq!(lamports(&index, &key, Commitment::Processed), None);
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), None);
+    }
+
+    #[test]
+    fn pending_write_moves_account_between_programs() {
+        let (index, key, owner) = rooted_index(10);
+        let new_owner = random_key();
+        index.process_slot(11, Some(10));
+        index.insert_processed(key, account(new_owner, 1, 11));
+
+        let at = |program, commitment| {
+            index
+                .get_program_accounts_with_commitment(program, commitment)
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        };
+        assert!(at(&owner, Commitment::Processed).is_empty());
+        assert_eq!(at(&new_owner, Commitment::Processed), vec![key]);
+        assert_eq!(at(&owner, Commitment::Finalized), vec![key]);
+        assert!(at(&new_owner, Commitment::Finalized).is_empty());
+    }
+
+    #[test]
+    fn write_at_or_below_root_goes_straight_to_the_shards() {
+        let (index, key, owner) = rooted_index(10);
+        index.insert_processed(key, account(owner, 9, 10));
+        assert_eq!(lamports(&index, &key, Commitment::Finalized), Some(9));
+        assert!(index.pending_snapshot().is_empty());
+    }
+
+    #[test]
+    fn token_lookups_overlay_pending_writes() {
+        let (index, _, _) = rooted_index(10);
+        let (mint, wallet, buyer) = (
+            random_key(),
+            random_key(),
+            random_key(),
+        );
+        let (kept, sold) = (random_key(), random_key());
+        index.insert(kept, token_account(&mint, &wallet, 5, 10));
+        index.insert(sold, token_account(&mint, &wallet, 5, 10));
+        index.process_slot(11, Some(10));
+        index.insert_processed(kept, token_account(&mint, &wallet, 3, 11));
+        index.insert_processed(sold, token_account(&mint, &buyer, 5, 11));
+
+        let held = |wallet, commitment| {
+            index
+                .get_token_accounts_by_owner(wallet, commitment)
+                .into_iter()
+                .map(|(key, acc)| {
+                    let token = TokenAccount::unpack(&acc.owner, &acc.data).unwrap();
+                    (key, token.amount)
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut both = vec![(kept, 5), (sold, 5)];
+        both.sort();
+        assert_eq!(held(&wallet, Commitment::Finalized), both);
+        assert_eq!(held(&wallet, Commitment::Processed), vec![(kept, 3)]);
+        assert_eq!(held(&buyer, Commitment::Processed), vec![(sold, 5)]);
+        assert!(held(&buyer, Commitment::Confirmed).is_empty());
+        assert_eq!(
+            index
+                .get_token_accounts_by_mint(&mint, Commitment::Processed)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn slot_listeners_hear_each_advance_once() {
+        let slots = SlotTracker::default();
+        let heard = Arc::new(RwLock::new(Vec::new()));
+        let sink = heard.clone();
+        slots.on_advance(move |slot, commitment| sink.write().unwrap().push((slot, commitment)));
+
+        slots.update(3, Commitment::Processed);
+        slots.update(2, Commitment::Processed);
+        slots.update(3, Commitment::Confirmed);
+        slots.update(4, Commitment::Finalized);
+        assert_eq!(
+            *heard.read().unwrap(),
+            [
+                (3, Commitment::Processed),
+                (3, Commitment::Confirmed),
+                (4, Commitment::Processed),
+                (4, Commitment::Confirmed),
+                (4, Commitment::Finalized),
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_reports_corrupted_secondary_indexes() {
+        let (index, _, _) = rooted_index(10);
+        let (mint, wallet, delegate) = (
+            random_key(),
+            random_key(),
+            random_key(),
+        );
+        index.register_memcmp_index(token::TOKEN_PROGRAM_ID, 0, 32);
+        let key = random_key();
+        let mut acc = token_account(&mint, &wallet, 5, 10);
+        acc.account.data[72] = 1;
+        acc.account.data[76..108].copy_from_slice(delegate.as_ref());
+        index.insert(key, acc);
+        assert!(index.audit().is_consistent());
+
+        index.wallet_index.remove(&wallet, &key);
+        index.delegate_index.insert(random_key(), key);
+        index.amount_index.remove(&mint, 5, &key);
+        index.amount_index.insert(mint, 6, key);
+        let memcmp = index
+            .filter_index
+            .find(&token::TOKEN_PROGRAM_ID, 0, mint.as_ref())
+            .unwrap();
+        memcmp.insert(random_key(), mint.as_ref());
+
+        let audit = index.audit();
+        assert_eq!(audit.accounts, 2);
+        let mut missing = audit.missing.clone();
+        missing.sort_by_key(|e| format!("{e:?}"));
+        assert_eq!(
+            missing,
+            [
+                IndexEntry::Amount { mint, amount: 5, key },
+                IndexEntry::Wallet { wallet, key },
+            ]
+        );
+        assert_eq!(audit.stale.len(), 3);
+        assert!(audit
+            .stale
+            .contains(&IndexEntry::Amount { mint, amount: 6, key }));
+        assert!(audit
+            .stale
+            .iter()
+            .any(|e| matches!(e, IndexEntry::Delegate { key: k, .. } if *k == key)));
+        assert!(audit
+            .stale
+            .iter()
+            .any(|e| matches!(e, IndexEntry::Memcmp { key: k, .. } if *k != key)));
+    }
+
+    #[test]
+    fn owner_and_mint_lookup_walks_either_list() {
+        let (index, _, _) = rooted_index(10);
+        let (wallet, mint, other_mint) = (
+            random_key(),
+            random_key(),
+            random_key(),
+        );
+        let held = random_key();
+        index.insert(held, token_account(&mint, &wallet, 1, 10));
+        // More accounts of `other_mint` for `wallet` than of `mint` overall,
+        // and more of `mint` overall than `wallet` holds.
+        for _ in 0..3 {
+            index.insert(random_key(), token_account(&other_mint, &wallet, 1, 10));
+        }
+        let lookup = |wallet, mint| {
+            index
+                .get_token_accounts_by_owner_and_mint(wallet, mint, Commitment::Finalized)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lookup(&wallet, &mint), [held]);
+        for _ in 0..5 {
+            index.insert(random_key(), token_account(&mint, &random_key(), 1, 10));
+        }
+        assert_eq!(lookup(&wallet, &mint), [held]);
+        let stranger = random_key();
+        assert!(lookup(&stranger, &mint).is_empty());
+        assert_eq!(lookup(&wallet, &other_mint).len(), 3);
+    }
 }
@@ -0,0 +1,462 @@
+//! Secondary index from an owner to the set of keys it owns, and a ranked
+//! variant for top‑N queries.
+//!
+//! Each owner's keys live in a [`KeySet`]: sorted sets partitioned by the
+//! leading byte of the key. Pubkeys are uniformly distributed, so writes to
+//! one huge owner (the SPL Token program) spread over many locks, inserts and
+//! removals are O(log n), and concatenating the partitions in order yields
+//! the keys sorted, which gives stable pagination cursors.
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::BTreeSet,
+    hash::Hash,
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
+
+/// Key‑range partitions per owner. Must divide 256.
+const RANGES: usize = 64;
+
+/// Keys of one owner, sorted and split into key ranges.
+pub struct KeySet {
+    ranges: Box<[RwLock<BTreeSet<Pubkey>>]>,
+}
+
+impl Default for KeySet {
+    fn default() -> Self {
+        Self::with_ranges(RANGES)
+    }
+}
+
+impl KeySet {
+    /// A set split into `ranges` partitions (a power of two, at most 256).
+    /// Small sets can use a single partition to save memory.
+    pub fn with_ranges(ranges: usize) -> Self {
+        assert!(ranges.is_power_of_two() && ranges <= 256);
+        Self {
+            ranges: (0..ranges).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    fn range(&self, key: &Pubkey) -> &RwLock<BTreeSet<Pubkey>> {
+        &self.ranges[key.as_ref()[0] as usize * self.ranges.len() / 256]
+    }
+
+    /// Add `key`; `false` if it was already present.
+    pub fn insert(&self, key: Pubkey) -> bool {
+        self.range(&key).write().unwrap().insert(key)
+    }
+
+    /// Remove `key`; `false` if it was not present.
+    pub fn remove(&self, key: &Pubkey) -> bool {
+        self.range(key).write().unwrap().remove(key)
+    }
+
+    pub fn contains(&self, key: &Pubkey) -> bool {
+        self.range(key).read().unwrap().contains(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|r| r.read().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.iter().all(|r| r.read().unwrap().is_empty())
+    }
+
+    /// All keys, in ascending order.
+    pub fn keys(&self) -> Vec<Pubkey> {
+        self.keys_after(None, usize::MAX)
+    }
+
+    /// Up to `limit` keys strictly greater than `after` (from the start if
+    /// `None`), in ascending order. Each partition is locked only while it
+    /// is being copied.
+    pub fn keys_after(&self, after: Option<&Pubkey>, limit: usize) -> Vec<Pubkey> {
+        let (first, lower) = match after {
+            Some(after) => (
+                after.as_ref()[0] as usize * self.ranges.len() / 256,
+                Bound::Excluded(after),
+            ),
+            None => (0, Bound::Unbounded),
+        };
+        let mut out = Vec::new();
+        for (i, range) in self.ranges.iter().enumerate().skip(first) {
+            let lower = if i == first { lower } else { Bound::Unbounded };
+            let set = range.read().unwrap();
+            out.extend(
+                set.range::<Pubkey, _>((lower, Bound::Unbounded))
+                    .take(limit - out.len())
+                    .copied(),
+            );
+            if out.len() == limit {
+                break;
+            }
+        }
+        out
+    }
+}
+
+/// Owner → [`KeySet`]. Empty sets are dropped so the map only holds owners
+/// that currently own something. The owner is a pubkey unless another group
+/// key is given (e.g. the bytes of a memcmp window).
+pub struct OwnerIndex<G = Pubkey> {
+    owners: DashMap<G, Arc<KeySet>>,
+    /// Partitions given to each new [`KeySet`].
+    ranges: usize,
+}
+
+impl<G: Eq + Hash + Clone> Default for OwnerIndex<G> {
+    fn default() -> Self {
+        Self::with_ranges(RANGES)
+    }
+}
+
+impl<G: Eq + Hash + Clone> OwnerIndex<G> {
+    /// An index whose per‑owner sets use `ranges` partitions (see
+    /// [`KeySet::with_ranges`]).
+    pub fn with_ranges(ranges: usize) -> Self {
+        Self {
+            owners: DashMap::new(),
+            ranges,
+        }
+    }
+
+    /// Record that `owner` owns `key`; `false` if it already was recorded.
+    pub fn insert(&self, owner: G, key: Pubkey) -> bool {
+        // Mutating under the map guard keeps `remove` from dropping the set
+        // between the lookup and the insert. A shared guard suffices, so
+        // writers to the same owner only contend on the key range.
+        if let Some(set) = self.owners.get(&owner) {
+            return set.insert(key);
+        }
+        self.owners
+            .entry(owner)
+            .or_insert_with(|| Arc::new(KeySet::with_ranges(self.ranges)))
+            .insert(key)
+    }
+
+    /// Forget that `owner` owns `key`; `false` if it was not recorded.
+    pub fn remove(&self, owner: &G, key: &Pubkey) -> bool {
+        let emptied = match self.owners.get(owner) {
+            Some(set) => {
+                if !set.remove(key) {
+                    return false;
+                }
+                set.is_empty()
+            }
+            None => return false,
+        };
+        if emptied {
+            // Inserts hold a guard on the map, so the set cannot refill while
+            // the exclusive entry is held.
+            if let Entry::Occupied(e) = self.owners.entry(owner.clone()) {
+                if e.get().is_empty() {
+                    e.remove();
+                }
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, owner: &G, key: &Pubkey) -> bool {
+        self.owners
+            .get(owner)
+            .is_some_and(|set| set.contains(key))
+    }
+
+    /// The key set of `owner`, if it owns anything. The handle stays valid
+    /// after the map guard is released.
+    pub fn get(&self, owner: &G) -> Option<Arc<KeySet>> {
+        self.owners.get(owner).map(|set| set.clone())
+    }
+
+    /// Every `(owner, key set)` pair; the map is not locked while the caller
+    /// walks the sets.
+    pub fn owners(&self) -> Vec<(G, Arc<KeySet>)> {
+        self.owners
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+}
+
+/// Partitions of each [`RankedIndex`] group. Must divide 256.
+const RANKED_RANGES: usize = 16;
+
+/// Group → `(rank, key)` pairs kept in rank order, for "top N" queries that
+/// must not scan the group (largest token accounts of a mint). Ranks are not
+/// uniformly distributed, so unlike [`OwnerIndex`] a group cannot be split
+/// into rank ranges. It is split by the leading byte of the key instead:
+/// balance changes on one popular mint spread over [`RANKED_RANGES`] locks,
+/// and [`top`](Self::top) merges the top of every partition.
+#[derive(Default)]
+pub struct RankedIndex {
+    groups: DashMap<Pubkey, Arc<RankedSet>>,
+}
+
+/// `(rank, key)` pairs of one group, each partition sorted by rank. A key
+/// always lands in the same partition, so moving it to a new rank never
+/// crosses locks.
+struct RankedSet {
+    ranges: Box<[RwLock<RankedRange>]>,
+}
+
+type RankedRange = BTreeSet<(u64, Pubkey)>;
+
+impl Default for RankedSet {
+    fn default() -> Self {
+        Self {
+            ranges: (0..RANKED_RANGES).map(|_| RwLock::default()).collect(),
+        }
+    }
+}
+
+impl RankedSet {
+    fn range(&self, key: &Pubkey) -> &RwLock<RankedRange> {
+        &self.ranges[key.as_ref()[0] as usize * self.ranges.len() / 256]
+    }
+
+    fn insert(&self, rank: u64, key: Pubkey) {
+        self.range(&key).write().unwrap().insert((rank, key));
+    }
+
+    /// `false` if `key` was not recorded at `rank`.
+    fn remove(&self, rank: u64, key: &Pubkey) -> bool {
+        self.range(key).write().unwrap().remove(&(rank, *key))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.iter().all(|r| r.read().unwrap().is_empty())
+    }
+
+    fn entries(&self) -> Vec<(u64, Pubkey)> {
+        self.ranges
+            .iter()
+            .flat_map(|r| r.read().unwrap().iter().copied().collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// The `limit` highest entries: the top `limit` of each partition,
+    /// merged. Each partition is locked only while it is being copied.
+    fn top(&self, limit: usize) -> Vec<(u64, Pubkey)> {
+        let mut out: Vec<(u64, Pubkey)> = self
+            .ranges
+            .iter()
+            .flat_map(|r| {
+                let set = r.read().unwrap();
+                set.iter().rev().take(limit).copied().collect::<Vec<_>>()
+            })
+            .collect();
+        out.sort_unstable_by(|a, b| b.cmp(a));
+        out.truncate(limit);
+        out
+    }
+}
+
+impl RankedIndex {
+    /// Record `key` at `rank` in `group`.
+    pub fn insert(&self, group: Pubkey, rank: u64, key: Pubkey) {
+        // Same guard discipline as `OwnerIndex::insert`.
+        if let Some(set) = self.groups.get(&group) {
+            set.insert(rank, key);
+            return;
+        }
+        self.groups.entry(group).or_default().insert(rank, key);
+    }
+
+    /// Forget `key` at `rank` in `group`.
+    pub fn remove(&self, group: &Pubkey, rank: u64, key: &Pubkey) {
+        let emptied = match self.groups.get(group) {
+            Some(set) => set.remove(rank, key) && set.is_empty(),
+            None => false,
+        };
+        if emptied {
+            if let Entry::Occupied(e) = self.groups.entry(*group) {
+                if e.get().is_empty() {
+                    e.remove();
+                }
+            }
+        }
+    }
+
+    /// Every `(group, rank, key)` entry, for audits.
+    pub fn entries(&self) -> Vec<(Pubkey, u64, Pubkey)> {
+        let groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|e| (*e.key(), e.value().clone()))
+            .collect();
+        groups
+            .into_iter()
+            .flat_map(|(group, set)| {
+                set.entries()
+                    .into_iter()
+                    .map(move |(rank, key)| (group, rank, key))
+            })
+            .collect()
+    }
+
+    /// Up to `limit` entries of `group`, highest rank first (ties by key,
+    /// descending).
+    pub fn top(&self, group: &Pubkey, limit: usize) -> Vec<(u64, Pubkey)> {
+        self.groups
+            .get(group)
+            .map(|set| set.top(limit))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_key() -> Pubkey {
+        Pubkey::new_from_array(rand::random())
+    }
+
+    /// A key whose leading byte is `first`, the rest random.
+    fn key_in(first: u8) -> Pubkey {
+        let mut bytes: [u8; 32] = rand::random();
+        bytes[0] = first;
+        Pubkey::new_from_array(bytes)
+    }
+
+    fn sorted(keys: &[Pubkey]) -> Vec<Pubkey> {
+        let mut keys = keys.to_vec();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn insert_and_remove_span_partitions() {
+        let set = KeySet::default();
+        let keys: Vec<Pubkey> = (0..1000).map(|_| random_key()).collect();
+        for k in &keys {
+            assert!(set.insert(*k));
+            assert!(!set.insert(*k));
+        }
+        assert!(set.ranges.iter().all(|r| !r.read().unwrap().is_empty()));
+        assert_eq!(set.len(), keys.len());
+        assert_eq!(set.keys(), sorted(&keys));
+
+        for k in &keys[..500] {
+            assert!(set.remove(k));
+            assert!(!set.remove(k));
+            assert!(!set.contains(k));
+        }
+        assert!(keys[500..].iter().all(|k| set.contains(k)));
+        assert_eq!(set.keys(), sorted(&keys[500..]));
+    }
+
+    #[test]
+    fn edge_keys_land_in_the_outer_partitions() {
+        let (low, high) = (
+            Pubkey::new_from_array([0; 32]),
+            Pubkey::new_from_array([0xff; 32]),
+        );
+        for ranges in [1, 2, RANGES, 256] {
+            let set = KeySet::with_ranges(ranges);
+            set.insert(high);
+            set.insert(low);
+            assert_eq!(set.ranges[0].read().unwrap().first(), Some(&low));
+            assert_eq!(set.ranges[ranges - 1].read().unwrap().last(), Some(&high));
+            assert_eq!(set.keys(), [low, high]);
+            assert_eq!(set.keys_after(Some(&low), 10), [high]);
+            assert!(set.keys_after(Some(&high), 10).is_empty());
+
+            assert!(set.remove(&low));
+            assert!(set.remove(&high));
+            assert!(set.is_empty());
+        }
+    }
+
+    #[test]
+    fn keys_after_walks_across_partitions() {
+        let set = KeySet::default();
+        let keys: Vec<Pubkey> = (0..=255).flat_map(|b| [key_in(b), key_in(b)]).collect();
+        for k in &keys {
+            set.insert(*k);
+        }
+        let all = sorted(&keys);
+
+        // Pages of every size reassemble the whole set.
+        for limit in [1, 3, 7, 64, 511, 512, 1000] {
+            let mut walked = Vec::new();
+            let mut after = None;
+            loop {
+                let page = set.keys_after(after.as_ref(), limit);
+                assert!(page.len() <= limit);
+                walked.extend_from_slice(&page);
+                if page.len() < limit {
+                    break;
+                }
+                after = page.last().copied();
+            }
+            assert_eq!(walked, all, "limit {limit}");
+        }
+
+        // A cursor that is no longer (or never was) in the set still works.
+        for cursor in [key_in(0x00), key_in(0x7f), key_in(0x80), key_in(0xff)] {
+            let expected: Vec<Pubkey> = all.iter().filter(|k| **k > cursor).copied().collect();
+            assert_eq!(set.keys_after(Some(&cursor), usize::MAX), expected);
+        }
+        // The last key of a partition continues at the next one.
+        let last_of_first = *set.ranges[0].read().unwrap().last().unwrap();
+        let first_of_second = *set.ranges[1].read().unwrap().first().unwrap();
+        assert_eq!(set.keys_after(Some(&last_of_first), 1), [first_of_second]);
+    }
+
+    #[test]
+    fn owner_index_drops_emptied_owners() {
+        let index = OwnerIndex::default();
+        let (owner, other) = (random_key(), random_key());
+        let keys: Vec<Pubkey> = (0..100).map(|_| random_key()).collect();
+        for k in &keys {
+            assert!(index.insert(owner, *k));
+        }
+        assert!(!index.insert(owner, keys[0]));
+        assert!(!index.remove(&other, &keys[0]));
+        assert!(index.contains(&owner, &keys[0]));
+        assert!(!index.contains(&other, &keys[0]));
+        assert_eq!(index.get(&owner).unwrap().keys(), sorted(&keys));
+
+        for k in &keys {
+            assert!(index.remove(&owner, k));
+        }
+        assert!(index.get(&owner).is_none());
+        assert!(index.owners().is_empty());
+    }
+
+    #[test]
+    fn ranked_top_merges_every_partition() {
+        let index = RankedIndex::default();
+        let group = random_key();
+        // Few distinct ranks, so ties are broken by key across partitions.
+        let mut entries: Vec<(u64, Pubkey)> = (0..2000).map(|i| (i % 37, random_key())).collect();
+        for (rank, key) in &entries {
+            index.insert(group, *rank, *key);
+        }
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+        for limit in [0, 1, 20, 100, 2000, 5000] {
+            let expected = &entries[..limit.min(entries.len())];
+            assert_eq!(index.top(&group, limit), expected, "limit {limit}");
+        }
+        assert_eq!(index.entries().len(), entries.len());
+        assert!(index.top(&random_key(), 20).is_empty());
+
+        // Moving a key to a new rank keeps it in its partition.
+        let (rank, key) = entries[entries.len() - 1];
+        index.remove(&group, rank, &key);
+        index.insert(group, u64::MAX, key);
+        assert_eq!(index.top(&group, 1), [(u64::MAX, key)]);
+
+        index.remove(&group, u64::MAX, &key);
+        for (rank, key) in &entries[..entries.len() - 1] {
+            index.remove(&group, *rank, key);
+        }
+        assert!(index.groups.is_empty());
+    }
+}
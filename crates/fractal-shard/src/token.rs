@@ -0,0 +1,307 @@
+//! Just enough of the SPL Token and Token‑2022 account layouts to maintain
+//! the token secondary indexes and answer token‑amount queries.
+//!
+//! ```text
+//! account: mint[32] owner[32] amount:u64 delegate:COption<Pubkey>[36] state:u8 …
+//! mint:    mint_authority:COption<Pubkey>[36] supply:u64 decimals:u8
+//!          is_initialized:u8 freeze_authority:COption<Pubkey>[36]
+//! ```
+//!
+//! Token‑2022 accounts share the same 165‑byte base and mints the same
+//! 82‑byte base. Either may be extended: the base is padded to 165 bytes and
+//! followed by an account‑type byte (1 = mint, 2 = account) and a list of
+//! `type:u16 length:u16 value[length]` TLV entries.
+
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+/// The SPL Token program.
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// The Token‑2022 (token extensions) program.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of a token account without extensions.
+pub const ACCOUNT_LEN: usize = 165;
+
+/// Size of a mint without extensions.
+pub const MINT_LEN: usize = 82;
+
+/// Size of a multisig account, which Token‑2022 never extends.
+const MULTISIG_LEN: usize = 355;
+
+const STATE_OFFSET: usize = 108;
+
+/// Token‑2022 account‑type byte, right after the base account.
+const ACCOUNT_TYPE_OFFSET: usize = ACCOUNT_LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// `true` for the programs whose accounts use the token account layout.
+pub fn is_token_program(program: &Pubkey) -> bool {
+    *program == TOKEN_PROGRAM_ID || *program == TOKEN_2022_PROGRAM_ID
+}
+
+/// The indexed fields of a token account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub mint: Pubkey,
+    /// Wallet (authority) that owns the tokens, not the program.
+    pub owner: Pubkey,
+    /// Balance in base units.
+    pub amount: u64,
+    /// Account allowed to transfer up to its delegated amount, if any.
+    pub delegate: Option<Pubkey>,
+}
+
+impl TokenAccount {
+    /// Parse `data` if it is an initialized token account owned by one of
+    /// the token programs. Mints, multisigs, uninitialized accounts and
+    /// Token‑2022 accounts with malformed extension data give `None`.
+    pub fn unpack(program: &Pubkey, data: &[u8]) -> Option<Self> {
+        if *program == TOKEN_PROGRAM_ID {
+            if data.len() != ACCOUNT_LEN {
+                return None;
+            }
+        } else if *program == TOKEN_2022_PROGRAM_ID {
+            if !is_valid_layout(data, ACCOUNT_TYPE_ACCOUNT) {
+                return None;
+            }
+        } else {
+            return None;
+        }
+        // 0 = uninitialized; mint and owner are still zeroed.
+        if data[STATE_OFFSET] == 0 {
+            return None;
+        }
+        Some(Self {
+            mint: Pubkey::new_from_array(data[0..32].try_into().unwrap()),
+            owner: Pubkey::new_from_array(data[32..64].try_into().unwrap()),
+            amount: u64::from_le_bytes(data[64..72].try_into().unwrap()),
+            delegate: coption_pubkey(&data[72..108]),
+        })
+    }
+}
+
+/// `true` if `data` has the shape of a token account of either program (what
+/// the `tokenAccountState` filter checks): an initialized account of exactly
+/// [`ACCOUNT_LEN`] bytes, or a Token‑2022 account with extensions. Multisigs
+/// can carry the account‑type byte by chance and are ruled out by length.
+pub fn is_token_account_data(data: &[u8]) -> bool {
+    (data.len() == ACCOUNT_LEN && data[STATE_OFFSET] != 0)
+        || (data.len() != MULTISIG_LEN
+            && data.get(ACCOUNT_TYPE_OFFSET) == Some(&ACCOUNT_TYPE_ACCOUNT))
+}
+
+/// Decode a `COption<Pubkey>`: a u32 tag (1 = `Some`) and the key.
+fn coption_pubkey(data: &[u8]) -> Option<Pubkey> {
+    match data[0..4] {
+        [1, 0, 0, 0] => Some(Pubkey::new_from_array(data[4..36].try_into().unwrap())),
+        _ => None,
+    }
+}
+
+/// The fields of a mint needed to render token amounts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mint {
+    pub supply: u64,
+    pub decimals: u8,
+}
+
+impl Mint {
+    /// Parse `data` if it is an initialized mint owned by one of the token
+    /// programs.
+    pub fn unpack(program: &Pubkey, data: &[u8]) -> Option<Self> {
+        if *program == TOKEN_PROGRAM_ID {
+            if data.len() != MINT_LEN {
+                return None;
+            }
+        } else if *program == TOKEN_2022_PROGRAM_ID {
+            if !is_valid_layout(data, ACCOUNT_TYPE_MINT) {
+                return None;
+            }
+            // Extended mints are zero‑padded up to the account size.
+            if data.len() != MINT_LEN && data[MINT_LEN..ACCOUNT_LEN].iter().any(|b| *b != 0) {
+                return None;
+            }
+        } else {
+            return None;
+        }
+        if data[45] == 0 {
+            return None;
+        }
+        Some(Self {
+            supply: u64::from_le_bytes(data[36..44].try_into().unwrap()),
+            decimals: data[44],
+        })
+    }
+}
+
+/// `true` if `data` is a Token‑2022 account of `account_type`: the bare
+/// base layout, or the extended one with well‑formed TLV entries.
+fn is_valid_layout(data: &[u8], account_type: u8) -> bool {
+    let base_len = match account_type {
+        ACCOUNT_TYPE_MINT => MINT_LEN,
+        _ => ACCOUNT_LEN,
+    };
+    if data.len() == base_len {
+        return true;
+    }
+    if data.len() <= ACCOUNT_TYPE_OFFSET
+        || data.len() == MULTISIG_LEN
+        || data[ACCOUNT_TYPE_OFFSET] != account_type
+    {
+        return false;
+    }
+
+    let mut tlv = &data[ACCOUNT_TYPE_OFFSET + 1..];
+    while tlv.len() >= 4 {
+        let ty = u16::from_le_bytes([tlv[0], tlv[1]]);
+        // Type 0 (`Uninitialized`) marks the start of unused space.
+        if ty == 0 {
+            break;
+        }
+        let len = u16::from_le_bytes([tlv[2], tlv[3]]) as usize;
+        match tlv.get(4 + len..) {
+            Some(rest) => tlv = rest,
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An initialized token account's base layout.
+    fn account_base(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[STATE_OFFSET] = 1;
+        data
+    }
+
+    /// An initialized mint's base layout.
+    fn mint_base(supply: u64, decimals: u8) -> Vec<u8> {
+        let mut data = vec![0u8; MINT_LEN];
+        data[36..44].copy_from_slice(&supply.to_le_bytes());
+        data[44] = decimals;
+        data[45] = 1;
+        data
+    }
+
+    /// Extend `data` the way Token‑2022 does: pad to the account size, then
+    /// the account‑type byte, the TLV `entries` and `spare` unused bytes.
+    fn extend(
+        mut data: Vec<u8>,
+        account_type: u8,
+        entries: &[(u16, &[u8])],
+        spare: usize,
+    ) -> Vec<u8> {
+        data.resize(ACCOUNT_LEN, 0);
+        data.push(account_type);
+        for (ty, value) in entries {
+            data.extend_from_slice(&ty.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            data.extend_from_slice(value);
+        }
+        data.resize(data.len() + spare, 0);
+        data
+    }
+
+    #[test]
+    fn extended_account_unpacks() {
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // ImmutableOwner (empty) and MemoTransfer (one flag byte).
+        let data = extend(
+            account_base(&mint, &owner, 42),
+            ACCOUNT_TYPE_ACCOUNT,
+            &[(7, &[]), (8, &[1])],
+            0,
+        );
+        let expected = TokenAccount {
+            mint,
+            owner,
+            amount: 42,
+            delegate: None,
+        };
+        assert_eq!(
+            TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data),
+            Some(expected)
+        );
+        // The classic program never extends.
+        assert_eq!(TokenAccount::unpack(&TOKEN_PROGRAM_ID, &data), None);
+        // Unused space after the last entry is zeroed.
+        let padded = extend(
+            account_base(&mint, &owner, 42),
+            ACCOUNT_TYPE_ACCOUNT,
+            &[(7, &[])],
+            9,
+        );
+        assert_eq!(
+            TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &padded),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn malformed_tlv_is_rejected() {
+        let base = account_base(&Pubkey::new_unique(), &Pubkey::new_unique(), 1);
+        // An entry whose length runs past the end of the data.
+        let mut data = extend(base.clone(), ACCOUNT_TYPE_ACCOUNT, &[(8, &[1])], 0);
+        let len = data.len();
+        data[len - 3..len - 1].copy_from_slice(&2u16.to_le_bytes());
+        assert_eq!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data), None);
+        // A mint's type byte on account data.
+        let data = extend(base.clone(), ACCOUNT_TYPE_MINT, &[(7, &[])], 0);
+        assert_eq!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data), None);
+        // Multisig‑sized data is never an extended account.
+        let mut data = extend(base, ACCOUNT_TYPE_ACCOUNT, &[], 0);
+        data.resize(MULTISIG_LEN, 0);
+        assert_eq!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data), None);
+    }
+
+    #[test]
+    fn extended_mint_unpacks() {
+        let close_authority = Pubkey::new_unique();
+        // MintCloseAuthority.
+        let data = extend(
+            mint_base(1_000, 6),
+            ACCOUNT_TYPE_MINT,
+            &[(3, close_authority.as_ref())],
+            0,
+        );
+        let expected = Mint {
+            supply: 1_000,
+            decimals: 6,
+        };
+        assert_eq!(Mint::unpack(&TOKEN_2022_PROGRAM_ID, &data), Some(expected));
+        assert_eq!(
+            Mint::unpack(&TOKEN_2022_PROGRAM_ID, &mint_base(1_000, 6)),
+            Some(expected)
+        );
+        assert_eq!(TokenAccount::unpack(&TOKEN_2022_PROGRAM_ID, &data), None);
+        // The padding between the mint and the type byte must be zero.
+        let mut dirty = data;
+        dirty[MINT_LEN] = 1;
+        assert_eq!(Mint::unpack(&TOKEN_2022_PROGRAM_ID, &dirty), None);
+    }
+
+    #[test]
+    fn multisig_is_not_token_account_data() {
+        let base = account_base(&Pubkey::new_unique(), &Pubkey::new_unique(), 1);
+        assert!(is_token_account_data(&base));
+        assert!(is_token_account_data(&extend(
+            base,
+            ACCOUNT_TYPE_ACCOUNT,
+            &[(7, &[])],
+            0
+        )));
+        // A multisig whose signer list puts a 2 at the account‑type offset.
+        let mut multisig = vec![0u8; MULTISIG_LEN];
+        multisig[ACCOUNT_TYPE_OFFSET] = ACCOUNT_TYPE_ACCOUNT;
+        assert!(!is_token_account_data(&multisig));
+    }
+}
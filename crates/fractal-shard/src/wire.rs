@@ -0,0 +1,406 @@
+//! Compact framed binary format used to stream updates from the Geyser plugin
+//! (`fractal_ingest`) to the RPC process.
+//!
+//! Every frame is `[u32 LE length][u8 tag][payload]`; `length` covers the tag
+//! and the payload. All integers are little endian.
+//!
+//! ```text
+//! ACCOUNT (tag 1): pubkey[32] owner[32] lamports:u64 rent_epoch:u64 slot:u64
+//!                  write_version:u64 flags:u8 [txn_signature[64]]
+//!                  data_len:u32 data[data_len]
+//!                  flags: bit 0 = executable, bit 1 = is_startup,
+//!                         bit 2 = txn_signature present
+//! SLOT    (tag 2): slot:u64 status:u8 (0 processed, 1 confirmed, 2 rooted)
+//!                  has_parent:u8 parent:u64
+//! END_OF_STARTUP (tag 3): no payload
+//! SHARD_SYNCED   (tag 4): shard:u32
+//! PENDING_RESET  (tag 5): no payload
+//! ```
+
+use crate::{Commitment, StoredAccount};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+use std::{
+    io::{self, ErrorKind},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Largest frame accepted by [`decode`]: a 10 MiB account plus its header.
+pub const MAX_FRAME_LEN: usize = 10 * 1024 * 1024 + 256;
+
+/// Default plugin listen address / rpc connect address.
+pub const DEFAULT_ENDPOINT: &str = "127.0.0.1:10015";
+
+const TAG_ACCOUNT: u8 = 1;
+const TAG_SLOT: u8 = 2;
+const TAG_END_OF_STARTUP: u8 = 3;
+const TAG_SHARD_SYNCED: u8 = 4;
+const TAG_PENDING_RESET: u8 = 5;
+
+const FLAG_EXECUTABLE: u8 = 1 << 0;
+const FLAG_STARTUP: u8 = 1 << 1;
+const FLAG_TXN_SIGNATURE: u8 = 1 << 2;
+
+/// One message on the ingest stream.
+#[derive(Clone, Debug)]
+pub enum Update {
+    Account {
+        pubkey: Pubkey,
+        account: StoredAccount,
+        is_startup: bool,
+    },
+    Slot {
+        slot: u64,
+        parent: Option<u64>,
+        /// `Finalized` means the slot was rooted.
+        status: Commitment,
+    },
+    /// Every startup snapshot account has been sent.
+    EndOfStartup,
+    /// Every rooted account of primary shard `shard` (see
+    /// [`ShardedIndex::shard_snapshot`](crate::ShardedIndex::shard_snapshot))
+    /// has been replayed since the previous `ShardSynced`; the receiver drops
+    /// the rooted accounts of that shard that were not.
+    ShardSynced { shard: u32 },
+    /// The sender's pending slots follow in full; the receiver drops its own.
+    PendingReset,
+}
+
+/// Where the plugin listens and the rpc process connects: `unix:<path>` for
+/// a Unix domain socket, anything else is a TCP `host:port`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("empty unix socket path".into()),
+            Some(path) => Ok(Endpoint::Unix(PathBuf::from(path))),
+            None if s.is_empty() => Err("empty endpoint".into()),
+            None => Ok(Endpoint::Tcp(s.trim_start_matches("tcp://").to_string())),
+        }
+    }
+}
+
+/// Append the length‑prefixed frame for `update` to `out`.
+pub fn encode(update: &Update, out: &mut Vec<u8>) {
+    match update {
+        Update::Account {
+            pubkey,
+            account,
+            is_startup,
+        } => encode_account(pubkey, account, *is_startup, out),
+        Update::Slot {
+            slot,
+            parent,
+            status,
+        } => encode_slot(*slot, *parent, *status, out),
+        Update::EndOfStartup => encode_end_of_startup(out),
+        Update::ShardSynced { shard } => encode_shard_synced(*shard, out),
+        Update::PendingReset => encode_pending_reset(out),
+    }
+}
+
+/// [`encode`] for an account without building an [`Update`] (no data copy).
+pub fn encode_account(
+    pubkey: &Pubkey,
+    account: &StoredAccount,
+    is_startup: bool,
+    out: &mut Vec<u8>,
+) {
+    let start = begin_frame(out, TAG_ACCOUNT);
+    out.extend_from_slice(pubkey.as_ref());
+    out.extend_from_slice(account.owner.as_ref());
+    out.extend_from_slice(&account.lamports.to_le_bytes());
+    out.extend_from_slice(&account.rent_epoch.to_le_bytes());
+    out.extend_from_slice(&account.slot.to_le_bytes());
+    out.extend_from_slice(&account.write_version.to_le_bytes());
+    let mut flags = 0;
+    if account.executable {
+        flags |= FLAG_EXECUTABLE;
+    }
+    if is_startup {
+        flags |= FLAG_STARTUP;
+    }
+    if account.txn_signature.is_some() {
+        flags |= FLAG_TXN_SIGNATURE;
+    }
+    out.push(flags);
+    if let Some(signature) = &account.txn_signature {
+        out.extend_from_slice(signature.as_ref());
+    }
+    out.extend_from_slice(&(account.data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&account.data);
+    end_frame(out, start);
+}
+
+/// [`encode`] for a slot status change.
+pub fn encode_slot(slot: u64, parent: Option<u64>, status: Commitment, out: &mut Vec<u8>) {
+    let start = begin_frame(out, TAG_SLOT);
+    out.extend_from_slice(&slot.to_le_bytes());
+    out.push(match status {
+        Commitment::Processed => 0,
+        Commitment::Confirmed => 1,
+        Commitment::Finalized => 2,
+    });
+    out.push(parent.is_some() as u8);
+    out.extend_from_slice(&parent.unwrap_or_default().to_le_bytes());
+    end_frame(out, start);
+}
+
+/// [`encode`] for the end‑of‑startup marker.
+pub fn encode_end_of_startup(out: &mut Vec<u8>) {
+    let start = begin_frame(out, TAG_END_OF_STARTUP);
+    end_frame(out, start);
+}
+
+/// [`encode`] for the end of a shard's replay.
+pub fn encode_shard_synced(shard: u32, out: &mut Vec<u8>) {
+    let start = begin_frame(out, TAG_SHARD_SYNCED);
+    out.extend_from_slice(&shard.to_le_bytes());
+    end_frame(out, start);
+}
+
+/// [`encode`] for the start of a pending replay.
+pub fn encode_pending_reset(out: &mut Vec<u8>) {
+    let start = begin_frame(out, TAG_PENDING_RESET);
+    end_frame(out, start);
+}
+
+fn begin_frame(out: &mut Vec<u8>, tag: u8) -> usize {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]); // length, patched by `end_frame`
+    out.push(tag);
+    start
+}
+
+fn end_frame(out: &mut [u8], start: usize) {
+    let len = (out.len() - start - 4) as u32;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+/// Decode one frame body (everything after the length prefix).
+pub fn decode(frame: &[u8]) -> io::Result<Update> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(invalid("frame too large"));
+    }
+    let mut r = Reader(frame);
+    let update = match r.u8()? {
+        TAG_ACCOUNT => {
+            let pubkey = r.pubkey()?;
+            let owner = r.pubkey()?;
+            let lamports = r.u64()?;
+            let rent_epoch = r.u64()?;
+            let slot = r.u64()?;
+            let write_version = r.u64()?;
+            let flags = r.u8()?;
+            let txn_signature = if flags & FLAG_TXN_SIGNATURE != 0 {
+                Some(r.signature()?)
+            } else {
+                None
+            };
+            let data_len = r.u32()? as usize;
+            let data = r.bytes(data_len)?.to_vec();
+            Update::Account {
+                pubkey,
+                account: StoredAccount::new(
+                    Account {
+                        lamports,
+                        data,
+                        owner,
+                        executable: flags & FLAG_EXECUTABLE != 0,
+                        rent_epoch,
+                    },
+                    slot,
+                    write_version,
+                )
+                .with_txn_signature(txn_signature),
+                is_startup: flags & FLAG_STARTUP != 0,
+            }
+        }
+        TAG_SLOT => {
+            let slot = r.u64()?;
+            let status = match r.u8()? {
+                0 => Commitment::Processed,
+                1 => Commitment::Confirmed,
+                2 => Commitment::Finalized,
+                _ => return Err(invalid("unknown slot status")),
+            };
+            let has_parent = r.u8()? != 0;
+            let parent = r.u64()?;
+            Update::Slot {
+                slot,
+                parent: has_parent.then_some(parent),
+                status,
+            }
+        }
+        TAG_END_OF_STARTUP => Update::EndOfStartup,
+        TAG_SHARD_SYNCED => Update::ShardSynced { shard: r.u32()? },
+        TAG_PENDING_RESET => Update::PendingReset,
+        _ => return Err(invalid("unknown frame tag")),
+    };
+    if !r.0.is_empty() {
+        return Err(invalid("trailing bytes in frame"));
+    }
+    Ok(update)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// Bounds‑checked cursor over a frame body.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(invalid("truncated frame"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn pubkey(&mut self) -> io::Result<Pubkey> {
+        Ok(Pubkey::new_from_array(self.bytes(32)?.try_into().unwrap()))
+    }
+
+    fn signature(&mut self) -> io::Result<Signature> {
+        Ok(Signature::from(<[u8; 64]>::try_from(self.bytes(64)?).unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode `update`, check the length prefix and decode the body.
+    fn round_trip(update: &Update) -> Update {
+        let mut out = Vec::new();
+        encode(update, &mut out);
+        let len = u32::from_le_bytes(out[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, out.len() - 4);
+        decode(&out[4..]).unwrap()
+    }
+
+    fn stored_account(txn_signature: Option<Signature>) -> StoredAccount {
+        let account = Account {
+            lamports: 42,
+            data: vec![1, 2, 3, 4, 5],
+            owner: Pubkey::new_unique(),
+            executable: true,
+            rent_epoch: 7,
+        };
+        StoredAccount::new(account, 99, 1234).with_txn_signature(txn_signature)
+    }
+
+    #[test]
+    fn account_round_trips() {
+        for (signature, is_startup) in [(None, false), (Some(Signature::from([9; 64])), true)] {
+            let pubkey = Pubkey::new_unique();
+            let account = stored_account(signature);
+            let update = Update::Account {
+                pubkey,
+                account: account.clone(),
+                is_startup,
+            };
+            let Update::Account {
+                pubkey: got_pubkey,
+                account: got,
+                is_startup: got_startup,
+            } = round_trip(&update)
+            else {
+                panic!("not an account frame");
+            };
+            assert_eq!(got_pubkey, pubkey);
+            assert_eq!(got.account, account.account);
+            assert_eq!(
+                (got.slot, got.write_version, got.txn_signature),
+                (account.slot, account.write_version, account.txn_signature)
+            );
+            assert_eq!(got_startup, is_startup);
+        }
+    }
+
+    #[test]
+    fn slot_round_trips() {
+        for status in Commitment::ALL {
+            for parent in [None, Some(41)] {
+                let Update::Slot {
+                    slot,
+                    parent: got_parent,
+                    status: got_status,
+                } = round_trip(&Update::Slot {
+                    slot: 42,
+                    parent,
+                    status,
+                })
+                else {
+                    panic!("not a slot frame");
+                };
+                assert_eq!((slot, got_parent, got_status), (42, parent, status));
+            }
+        }
+    }
+
+    #[test]
+    fn markers_round_trip() {
+        assert!(matches!(round_trip(&Update::EndOfStartup), Update::EndOfStartup));
+        assert!(matches!(round_trip(&Update::PendingReset), Update::PendingReset));
+        assert!(matches!(
+            round_trip(&Update::ShardSynced { shard: 4095 }),
+            Update::ShardSynced { shard: 4095 }
+        ));
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        let mut out = Vec::new();
+        encode_account(&Pubkey::new_unique(), &stored_account(None), false, &mut out);
+        let body = &out[4..];
+
+        let err = |frame: &[u8]| decode(frame).unwrap_err().kind();
+        assert_eq!(err(&body[..body.len() - 1]), ErrorKind::InvalidData);
+        assert_eq!(err(&[body, &[0]].concat()), ErrorKind::InvalidData);
+        assert_eq!(err(&[]), ErrorKind::InvalidData);
+        assert_eq!(err(&[0xff]), ErrorKind::InvalidData);
+        let mut slot = Vec::new();
+        encode_slot(1, None, Commitment::Processed, &mut slot);
+        slot[13] = 3; // status
+        assert_eq!(err(&slot[4..]), ErrorKind::InvalidData);
+        let oversized = vec![TAG_END_OF_STARTUP; MAX_FRAME_LEN + 1];
+        assert_eq!(err(&oversized), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn endpoints_parse() {
+        assert_eq!(
+            "unix:/tmp/fractal.sock".parse(),
+            Ok(Endpoint::Unix("/tmp/fractal.sock".into()))
+        );
+        assert_eq!(
+            "tcp://127.0.0.1:1".parse(),
+            Ok(Endpoint::Tcp("127.0.0.1:1".into()))
+        );
+        assert!("unix:".parse::<Endpoint>().is_err());
+        assert!("".parse::<Endpoint>().is_err());
+    }
+}
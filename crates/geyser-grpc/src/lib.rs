@@ -0,0 +1,7 @@
+//! Client (and, for tests, server) of the Yellowstone Geyser gRPC service,
+//! generated from the subset of its `geyser.proto` that the gRPC ingest mode
+//! uses.
+
+pub mod geyser {
+    tonic::include_proto!("geyser");
+}
@@ -0,0 +1,233 @@
+//! JSON configuration read from the file the validator passes to `on_load`.
+//!
+//! ```json
+//! {
+//!   "libpath": "/opt/fractal/libfractal_ingest.so",
+//!   "owner_allowlist": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"],
+//!   "owner_denylist": ["Vote111111111111111111111111111111111111111"],
+//!   "account_allowlist": [],
+//!   "include_startup": true,
+//!   "endpoint": "127.0.0.1:10015",
+//!   "publish_capacity": 65536,
+//!   "write_buffer_bytes": 1048576
+//! }
+//! ```
+//!
+//! Every key except `libpath` (which the validator itself uses) is optional.
+
+use {
+    fractal_shard::wire::{Endpoint, DEFAULT_ENDPOINT},
+    serde::Deserialize,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError as GeyserError, Result},
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashSet, fs},
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct PluginConfig {
+    /// Only index accounts owned by these programs (empty = no owner restriction).
+    pub owner_allowlist: Vec<String>,
+    /// Never index accounts owned by these programs. Takes precedence over
+    /// both allowlists.
+    pub owner_denylist: Vec<String>,
+    /// Always index these accounts, whatever their owner.
+    pub account_allowlist: Vec<String>,
+    /// Index the accounts streamed from the startup snapshot.
+    pub include_startup: bool,
+    /// Where the rpc process connects (`host:port` or `unix:<path>`).
+    pub endpoint: String,
+    /// Frames buffered per connected client before it is dropped as lagging.
+    pub publish_capacity: usize,
+    /// Socket write buffer per connected client.
+    pub write_buffer_bytes: usize,
+}
+
+impl Default for PluginConfig {
+    fn default() -> Self {
+        Self {
+            owner_allowlist: Vec::new(),
+            owner_denylist: Vec::new(),
+            account_allowlist: Vec::new(),
+            include_startup: true,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            publish_capacity: 1 << 16,
+            write_buffer_bytes: 1 << 20,
+        }
+    }
+}
+
+impl PluginConfig {
+    /// Read and parse the config file; an empty path yields the defaults.
+    pub fn load(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(path).map_err(|e| GeyserError::ConfigFileReadError {
+            msg: format!("cannot read {path}: {e}"),
+        })?;
+        serde_json::from_str(&raw).map_err(|e| GeyserError::ConfigFileReadError {
+            msg: format!("invalid config {path}: {e}"),
+        })
+    }
+
+    pub fn endpoint(&self) -> Result<Endpoint> {
+        self.endpoint
+            .parse()
+            .map_err(|msg| GeyserError::ConfigFileReadError { msg })
+    }
+
+    pub fn account_filter(&self) -> Result<AccountFilter> {
+        Ok(AccountFilter {
+            owner_allowlist: parse_keys("owner_allowlist", &self.owner_allowlist)?,
+            owner_denylist: parse_keys("owner_denylist", &self.owner_denylist)?,
+            account_allowlist: parse_keys("account_allowlist", &self.account_allowlist)?,
+        })
+    }
+}
+
+fn parse_keys(field: &str, keys: &[String]) -> Result<HashSet<Pubkey>> {
+    keys.iter()
+        .map(|k| {
+            k.parse().map_err(|_| GeyserError::ConfigFileReadError {
+                msg: format!("invalid pubkey in {field}: {k}"),
+            })
+        })
+        .collect()
+}
+
+/// Decides which accounts the plugin indexes and publishes.
+#[derive(Debug, Default)]
+pub struct AccountFilter {
+    owner_allowlist: HashSet<Pubkey>,
+    owner_denylist: HashSet<Pubkey>,
+    account_allowlist: HashSet<Pubkey>,
+}
+
+impl AccountFilter {
+    /// The denylist always wins. With both allowlists empty every other
+    /// account passes; otherwise an account must match at least one of them.
+    pub fn wants(&self, key: &Pubkey, owner: &Pubkey) -> bool {
+        if self.owner_denylist.contains(owner) {
+            return false;
+        }
+        if self.owner_allowlist.is_empty() && self.account_allowlist.is_empty() {
+            return true;
+        }
+        self.account_allowlist.contains(key) || self.owner_allowlist.contains(owner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    const VOTE: &str = "Vote111111111111111111111111111111111111111";
+
+    fn filter(
+        owner_allow: &[Pubkey],
+        owner_deny: &[Pubkey],
+        account_allow: &[Pubkey],
+    ) -> AccountFilter {
+        AccountFilter {
+            owner_allowlist: owner_allow.iter().copied().collect(),
+            owner_denylist: owner_deny.iter().copied().collect(),
+            account_allowlist: account_allow.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn full_config_parses() {
+        let path = std::env::temp_dir().join(format!("fractal-ingest-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            format!(
+                r#"{{
+                    "libpath": "/opt/fractal/libfractal_ingest.so",
+                    "owner_allowlist": ["{TOKEN}"],
+                    "owner_denylist": ["{VOTE}"],
+                    "account_allowlist": ["{VOTE}"],
+                    "include_startup": false,
+                    "endpoint": "unix:/tmp/fractal.sock",
+                    "publish_capacity": 1024,
+                    "write_buffer_bytes": 4096
+                }}"#
+            ),
+        )
+        .unwrap();
+        let config = PluginConfig::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.owner_allowlist, [TOKEN]);
+        assert_eq!(config.owner_denylist, [VOTE]);
+        assert_eq!(config.account_allowlist, [VOTE]);
+        assert!(!config.include_startup);
+        assert_eq!(config.endpoint, "unix:/tmp/fractal.sock");
+        assert_eq!(config.publish_capacity, 1024);
+        assert_eq!(config.write_buffer_bytes, 4096);
+        config.endpoint().unwrap();
+        config.account_filter().unwrap();
+    }
+
+    #[test]
+    fn minimal_config_takes_defaults() {
+        let config: PluginConfig =
+            serde_json::from_str(r#"{"libpath": "/opt/fractal/libfractal_ingest.so"}"#).unwrap();
+        let default = PluginConfig::load("").unwrap();
+        for config in [config, default] {
+            assert!(config.owner_allowlist.is_empty());
+            assert!(config.owner_denylist.is_empty());
+            assert!(config.account_allowlist.is_empty());
+            assert!(config.include_startup);
+            assert_eq!(config.endpoint, DEFAULT_ENDPOINT);
+            assert_eq!(config.publish_capacity, 1 << 16);
+            assert_eq!(config.write_buffer_bytes, 1 << 20);
+        }
+    }
+
+    #[test]
+    fn invalid_pubkey_names_the_field() {
+        let config = PluginConfig {
+            owner_denylist: vec![VOTE.to_string(), "not-a-key".to_string()],
+            ..PluginConfig::default()
+        };
+        match config.account_filter() {
+            Err(GeyserError::ConfigFileReadError { msg }) => {
+                assert_eq!(msg, "invalid pubkey in owner_denylist: not-a-key")
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_filter_wants_everything() {
+        let f = AccountFilter::default();
+        assert!(f.wants(&Pubkey::new_unique(), &Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn denylist_beats_both_allowlists() {
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let f = filter(&[owner], &[owner], &[key]);
+        assert!(!f.wants(&key, &owner));
+        // Without allowlists the denylist only drops its own owners.
+        let f = filter(&[], &[owner], &[]);
+        assert!(!f.wants(&key, &owner));
+        assert!(f.wants(&key, &Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn allowlists_are_a_union() {
+        let (key, owner, other) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let f = filter(&[owner], &[], &[key]);
+        assert!(f.wants(&key, &other));
+        assert!(f.wants(&other, &owner));
+        assert!(!f.wants(&other, &other));
+    }
+}
@@ -1,73 +1,205 @@
 //! Geyser plugin that feeds account updates into a shared `ShardedIndex`.
 //! Errors are reported back to the validator instead of panicking.
+//!
+//! Every applied update is also published to connected `fractal-rpc`
+//! processes over a local socket (see [`publisher`]). What gets indexed and
+//! where it is published is controlled by the config file (see [`config`]).
 
 use {
-    fractal_shard::ShardedIndex,
+    fractal_shard::{wire::Update, Commitment, ShardedIndex, SlotTracker, StoredAccount},
     solana_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPlugin, ReplicaAccountInfoVersions, Result, SlotStatus, Error as GeyserError,
+        GeyserPlugin, ReplicaAccountInfoVersions, Result, SlotStatus, GeyserPluginError as GeyserError,
     },
-    solana_sdk::{account::Account, pubkey::Pubkey},
-    std::sync::Arc,
+    solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature},
+    std::{fmt, sync::Arc},
     tokio::runtime::Runtime,
 };
 
+pub mod config;
+pub mod publisher;
+
+use {
+    config::{AccountFilter, PluginConfig},
+    publisher::Publisher,
+};
+
 pub struct FractalPlugin {
     index: Arc<ShardedIndex>,
     runtime: Runtime,
+    publisher: Option<Publisher>,
+    filter: AccountFilter,
+    include_startup: bool,
 }
 
 impl Default for FractalPlugin {
     fn default() -> Self {
         Self {
             index: Arc::new(ShardedIndex::default()),
-            runtime: tokio::runtime::Builder::new_current_thread()
+            runtime: tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .thread_name("fractal-ingest")
                 .enable_all()
                 .build()
                 .expect("tokio runtime"),
+            publisher: None,
+            filter: AccountFilter::default(),
+            include_startup: true,
         }
     }
 }
 
+impl fmt::Debug for FractalPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FractalPlugin")
+            .field("accounts", &self.index.len())
+            .field("include_startup", &self.include_startup)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FractalPlugin {
+    /// Latest processed / confirmed / rooted slot seen by the plugin.
+    pub fn slots(&self) -> Arc<SlotTracker> {
+        self.index.slots()
+    }
+}
+
 impl GeyserPlugin for FractalPlugin {
     fn name(&self) -> &'static str {
         "FractalIngest"
     }
 
+    fn on_load(&mut self, config_file: &str, _is_reload: bool) -> Result<()> {
+        let config = PluginConfig::load(config_file)?;
+        self.filter = config.account_filter()?;
+        self.include_startup = config.include_startup;
+
+        let publisher = Publisher::start(
+            &self.runtime,
+            &config.endpoint()?,
+            self.index.clone(),
+            config.publish_capacity,
+            config.write_buffer_bytes,
+        )
+        .map_err(|e| GeyserError::Custom(Box::new(e)))?;
+        self.publisher = Some(publisher);
+        Ok(())
+    }
+
+    fn on_unload(&mut self) {
+        self.publisher = None;
+    }
+
     fn update_account(
         &self,
         account: ReplicaAccountInfoVersions,
-        _slot: u64,
-        _is_startup: bool,
+        slot: u64,
+        is_startup: bool,
     ) -> Result<()> {
-        // Accept only the version we know; return a clear error for any future version.
-        let acc = match account {
-            ReplicaAccountInfoVersions::V0_0_3(a) => a,
-            _ => {
-                return Err(GeyserError::InvalidArgument(
-                    "Unsupported ReplicaAccountInfo version".into(),
-                ))
-            }
-        };
+        let acc = ReplicaAccount::from(account);
+
+        if is_startup && !self.include_startup {
+            return Ok(());
+        }
 
         // Convert the raw byte slices into `Pubkey`s, returning a proper Geyser error on failure.
         let key = Pubkey::try_from(acc.pubkey)
-            .map_err(|e| GeyserError::InvalidArgument(format!("bad pubkey: {e}")))?;
+            .map_err(|e| GeyserError::AccountsUpdateError {
+                msg: format!("bad pubkey: {e}"),
+            })?;
         let owner = Pubkey::try_from(acc.owner)
-            .map_err(|e| GeyserError::InvalidArgument(format!("bad owner: {e}")))?;
-
-        // Store the account. `data` is cloned because the slice is only valid for the duration
-        // of this callback.
-        self.index.insert(
-            key,
+            .map_err(|e| GeyserError::AccountsUpdateError {
+                msg: format!("bad owner: {e}"),
+            })?;
+        // An update the filter does not want still reaches an account we
+        // indexed: a close is usually reported with the system program as
+        // owner, and an account reassigned to an excluded owner must not stay
+        // listed under its old one. Either way the key is evicted.
+        let wanted = self.filter.wants(&key, &owner);
+        if !wanted
+            && self
+                .index
+                .get_with_commitment(&key, Commitment::Processed)
+                .is_none()
+        {
+            return Ok(());
+        }
+        let account = if wanted {
             Account {
                 lamports: acc.lamports,
                 data: acc.data.to_vec(),
                 owner,
                 executable: acc.executable,
                 rent_epoch: acc.rent_epoch,
-            },
+            }
+        } else {
+            // Zero lamports: stored (and published) as a close.
+            Account {
+                owner,
+                ..Account::default()
+            }
+        };
+
+        // Store the account. `data` is cloned because the slice is only valid for the duration
+        // of this callback. Stale deliveries (older slot / write_version) are dropped by the index.
+        // Snapshot accounts are already rooted; live updates wait for their slot to be rooted.
+        let stored = StoredAccount::new(account, slot, acc.write_version)
+            .with_txn_signature(acc.txn_signature);
+        // Encode before the account moves into the index; only sent if the index applied it.
+        let frame = self
+            .publisher
+            .as_ref()
+            .and_then(|p| p.account_frame(&key, &stored, is_startup));
+        let applied = if is_startup {
+            // Fast path: secondary indexes are built at `notify_end_of_startup`.
+            self.index.insert_startup(key, stored);
+            true
+        } else {
+            self.index.insert_processed(key, stored)
+        };
+
+        if let (true, Some(publisher), Some(frame)) = (applied, &self.publisher, frame) {
+            publisher.send(frame);
+        }
+
+        Ok(())
+    }
+
+    fn notify_end_of_startup(&self) -> Result<()> {
+        tracing::info!(
+            "startup snapshot complete: {} accounts, building secondary indexes",
+            self.index.startup_loaded()
         );
+        self.index.finish_startup();
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(&Update::EndOfStartup);
+        }
+        Ok(())
+    }
+
+    fn update_slot_status(&self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<()> {
+        let status = match status {
+            SlotStatus::Processed => {
+                self.index.process_slot(slot, parent);
+                Commitment::Processed
+            }
+            SlotStatus::Confirmed => {
+                self.index.confirm_slot(slot);
+                Commitment::Confirmed
+            }
+            SlotStatus::Rooted => {
+                self.index.root_slot(slot);
+                Commitment::Finalized
+            }
+        };
 
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(&Update::Slot {
+                slot,
+                parent,
+                status,
+            });
+        }
         Ok(())
     }
 
@@ -75,3 +207,221 @@ impl GeyserPlugin for FractalPlugin {
         true
     }
 }
+
+/// The fields of a `ReplicaAccountInfo` the plugin stores, borrowed from
+/// whichever interface version the validator delivered. `txn_signature` is
+/// `None` for V0_0_1, which predates it, and for snapshot accounts.
+struct ReplicaAccount<'a> {
+    pubkey: &'a [u8],
+    lamports: u64,
+    owner: &'a [u8],
+    executable: bool,
+    rent_epoch: u64,
+    data: &'a [u8],
+    write_version: u64,
+    txn_signature: Option<Signature>,
+}
+
+impl<'a> From<ReplicaAccountInfoVersions<'a>> for ReplicaAccount<'a> {
+    fn from(account: ReplicaAccountInfoVersions<'a>) -> Self {
+        match account {
+            ReplicaAccountInfoVersions::V0_0_1(a) => Self {
+                pubkey: a.pubkey,
+                lamports: a.lamports,
+                owner: a.owner,
+                executable: a.executable,
+                rent_epoch: a.rent_epoch,
+                data: a.data,
+                write_version: a.write_version,
+                txn_signature: None,
+            },
+            ReplicaAccountInfoVersions::V0_0_2(a) => Self {
+                pubkey: a.pubkey,
+                lamports: a.lamports,
+                owner: a.owner,
+                executable: a.executable,
+                rent_epoch: a.rent_epoch,
+                data: a.data,
+                write_version: a.write_version,
+                txn_signature: a.txn_signature.copied(),
+            },
+            ReplicaAccountInfoVersions::V0_0_3(a) => Self {
+                pubkey: a.pubkey,
+                lamports: a.lamports,
+                owner: a.owner,
+                executable: a.executable,
+                rent_epoch: a.rent_epoch,
+                data: a.data,
+                write_version: a.write_version,
+                txn_signature: a.txn.map(|txn| *txn.signature()),
+            },
+        }
+    }
+}
+
+/// Entry point the validator looks up when loading the plugin library.
+///
+/// # Safety
+/// Called once by the validator, which takes ownership of the returned plugin.
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub unsafe extern "C" fn _create_plugin() -> *mut dyn GeyserPlugin {
+    Box::into_raw(Box::new(FractalPlugin::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_geyser_plugin_interface::geyser_plugin_interface::{
+            ReplicaAccountInfo, ReplicaAccountInfoV2, ReplicaAccountInfoV3,
+        },
+        solana_sdk::transaction::{SanitizedTransaction, Transaction},
+    };
+
+    const KEY: [u8; 32] = [1; 32];
+    const OWNER: [u8; 32] = [2; 32];
+    const DATA: [u8; 3] = [7, 8, 9];
+
+    fn assert_fields(acc: &ReplicaAccount, txn_signature: Option<Signature>) {
+        assert_eq!(acc.pubkey, KEY);
+        assert_eq!(acc.lamports, 42);
+        assert_eq!(acc.owner, OWNER);
+        assert!(acc.executable);
+        assert_eq!(acc.rent_epoch, 3);
+        assert_eq!(acc.data, DATA);
+        assert_eq!(acc.write_version, 11);
+        assert_eq!(acc.txn_signature, txn_signature);
+    }
+
+    #[test]
+    fn v0_0_1_has_no_txn_signature() {
+        let info = ReplicaAccountInfo {
+            pubkey: &KEY,
+            lamports: 42,
+            owner: &OWNER,
+            executable: true,
+            rent_epoch: 3,
+            data: &DATA,
+            write_version: 11,
+        };
+        let acc = ReplicaAccount::from(ReplicaAccountInfoVersions::V0_0_1(&info));
+        assert_fields(&acc, None);
+    }
+
+    #[test]
+    fn v0_0_2_copies_txn_signature() {
+        let signature = Signature::new_unique();
+        let mut info = ReplicaAccountInfoV2 {
+            pubkey: &KEY,
+            lamports: 42,
+            owner: &OWNER,
+            executable: true,
+            rent_epoch: 3,
+            data: &DATA,
+            write_version: 11,
+            txn_signature: Some(&signature),
+        };
+        let acc = ReplicaAccount::from(ReplicaAccountInfoVersions::V0_0_2(&info));
+        assert_fields(&acc, Some(signature));
+
+        info.txn_signature = None;
+        let acc = ReplicaAccount::from(ReplicaAccountInfoVersions::V0_0_2(&info));
+        assert_fields(&acc, None);
+    }
+
+    #[test]
+    fn v0_0_3_takes_signature_of_txn() {
+        let signature = Signature::new_unique();
+        let mut tx = Transaction::new_with_payer(&[], Some(&Pubkey::new_unique()));
+        tx.signatures = vec![signature];
+        let txn = SanitizedTransaction::from_transaction_for_tests(tx);
+        let mut info = ReplicaAccountInfoV3 {
+            pubkey: &KEY,
+            lamports: 42,
+            owner: &OWNER,
+            executable: true,
+            rent_epoch: 3,
+            data: &DATA,
+            write_version: 11,
+            txn: Some(&txn),
+        };
+        let acc = ReplicaAccount::from(ReplicaAccountInfoVersions::V0_0_3(&info));
+        assert_fields(&acc, Some(signature));
+
+        // Snapshot accounts carry no transaction.
+        info.txn = None;
+        let acc = ReplicaAccount::from(ReplicaAccountInfoVersions::V0_0_3(&info));
+        assert_fields(&acc, None);
+    }
+
+    fn plugin(owner_allowlist: &[Pubkey]) -> FractalPlugin {
+        let config = PluginConfig {
+            owner_allowlist: owner_allowlist.iter().map(Pubkey::to_string).collect(),
+            ..PluginConfig::default()
+        };
+        let plugin = FractalPlugin {
+            filter: config.account_filter().unwrap(),
+            ..FractalPlugin::default()
+        };
+        plugin.index.finish_startup();
+        plugin
+    }
+
+    fn update(plugin: &FractalPlugin, key: &Pubkey, owner: &Pubkey, slot: u64) {
+        let info = ReplicaAccountInfoV2 {
+            pubkey: key.as_ref(),
+            lamports: 42,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 0,
+            data: &DATA,
+            write_version: slot,
+            txn_signature: None,
+        };
+        plugin
+            .update_account(ReplicaAccountInfoVersions::V0_0_2(&info), slot, false)
+            .unwrap();
+    }
+
+    #[test]
+    fn reassignment_to_an_excluded_owner_evicts_the_account() {
+        let (key, owner, excluded) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let plugin = plugin(&[owner]);
+        update(&plugin, &key, &owner, 11);
+        assert!(plugin
+            .index
+            .get_with_commitment(&key, Commitment::Processed)
+            .is_some());
+
+        update(&plugin, &key, &excluded, 12);
+        assert!(plugin
+            .index
+            .get_with_commitment(&key, Commitment::Processed)
+            .is_none());
+        plugin.index.root_slot(12);
+        assert!(plugin.index.get(&key).is_none());
+        assert!(plugin.index.get_program_accounts(&owner).is_empty());
+        assert!(plugin.index.get_program_accounts(&excluded).is_empty());
+    }
+
+    #[test]
+    fn unknown_account_of_an_excluded_owner_is_skipped() {
+        let (key, owner, excluded) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let plugin = plugin(&[owner]);
+        update(&plugin, &key, &excluded, 11);
+        assert!(plugin
+            .index
+            .get_with_commitment(&key, Commitment::Processed)
+            .is_none());
+        assert!(plugin.index.pending_snapshot().is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+//! Publishes account / slot updates to connected RPC processes using the
+//! framed format from `fractal_shard::wire`.
+//!
+//! A newly connected client first receives a replay of the plugin's own index
+//! and then the live stream:
+//!
+//! 1. the current root and every pending write per slot, after a marker that
+//!    tells the client to drop the pending writes it holds;
+//! 2. the rooted accounts shard by shard, each shard closed by a marker that
+//!    tells the client to drop the rooted accounts of that shard that were
+//!    not replayed (they were closed while it was away);
+//! 3. the end‑of‑startup marker if the snapshot is complete.
+//!
+//! The client is subscribed to live frames before the replay starts, and the
+//! frames queued meanwhile are forwarded between shards, so nothing is missed
+//! and a long replay does not make the client lag. Replay and live frames may
+//! overlap; the receiving index drops anything older than what it already
+//! holds. A client that falls more than the channel capacity behind is
+//! disconnected and resyncs on reconnect.
+
+use {
+    fractal_shard::{
+        wire::{self, Endpoint, Update},
+        Commitment, ShardedIndex, StoredAccount,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{io, sync::Arc},
+    tokio::{
+        io::{AsyncWrite, AsyncWriteExt, BufWriter},
+        net::TcpListener,
+        runtime::Runtime,
+        sync::broadcast::{
+            self,
+            error::{RecvError, TryRecvError},
+        },
+    },
+};
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+pub type Frame = Arc<Vec<u8>>;
+
+pub struct Publisher {
+    tx: broadcast::Sender<Frame>,
+}
+
+impl Publisher {
+    /// Bind `endpoint` and start accepting clients on `runtime`. `capacity`
+    /// frames are buffered per client; `write_buffer` is the per‑client
+    /// socket buffer in bytes.
+    pub fn start(
+        runtime: &Runtime,
+        endpoint: &Endpoint,
+        index: Arc<ShardedIndex>,
+        capacity: usize,
+        write_buffer: usize,
+    ) -> io::Result<Self> {
+        let (tx, _) = broadcast::channel(capacity);
+        let listener = runtime.block_on(Listener::bind(endpoint))?;
+        runtime.spawn(accept_loop(listener, tx.clone(), index, write_buffer));
+        tracing::info!("fractal ingest publishing on {:?}", endpoint);
+        Ok(Self { tx })
+    }
+
+    /// Queue `update` for every connected client. Never blocks the validator.
+    pub fn publish(&self, update: &Update) {
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        let mut frame = Vec::new();
+        wire::encode(update, &mut frame);
+        self.send(Arc::new(frame));
+    }
+
+    /// Encoded account frame, or `None` when nobody is connected.
+    pub fn account_frame(
+        &self,
+        pubkey: &Pubkey,
+        account: &StoredAccount,
+        is_startup: bool,
+    ) -> Option<Frame> {
+        if self.tx.receiver_count() == 0 {
+            return None;
+        }
+        let mut frame = Vec::new();
+        wire::encode_account(pubkey, account, is_startup, &mut frame);
+        Some(Arc::new(frame))
+    }
+
+    /// Queue an already encoded frame. Never blocks the validator.
+    pub fn send(&self, frame: Frame) {
+        // Clients may disconnect at any time; an unsent frame is not an error.
+        let _ = self.tx.send(frame);
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    async fn bind(endpoint: &Endpoint) -> io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                // A stale socket file from a previous run would make bind fail.
+                let _ = std::fs::remove_file(path);
+                Ok(Listener::Unix(UnixListener::bind(path)?))
+            }
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    async fn accept(&self) -> io::Result<Box<dyn AsyncWrite + Send + Unpin>> {
+        match self {
+            Listener::Tcp(l) => {
+                let (stream, _) = l.accept().await?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(l) => {
+                let (stream, _) = l.accept().await?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+async fn accept_loop(
+    listener: Listener,
+    tx: broadcast::Sender<Frame>,
+    index: Arc<ShardedIndex>,
+    write_buffer: usize,
+) {
+    loop {
+        match listener.accept().await {
+            Ok(stream) => {
+                // Subscribe before the replay so nothing published meanwhile is lost.
+                let rx = tx.subscribe();
+                let index = index.clone();
+                tokio::spawn(async move {
+                    tracing::info!("fractal ingest client connected");
+                    if let Err(e) = serve_client(stream, rx, index, write_buffer).await {
+                        tracing::warn!("fractal ingest client dropped: {e}");
+                    }
+                });
+            }
+            Err(e) => tracing::error!("fractal ingest accept failed: {e}"),
+        }
+    }
+}
+
+async fn serve_client(
+    stream: Box<dyn AsyncWrite + Send + Unpin>,
+    mut rx: broadcast::Receiver<Frame>,
+    index: Arc<ShardedIndex>,
+    write_buffer: usize,
+) -> io::Result<()> {
+    let mut stream = BufWriter::with_capacity(write_buffer, stream);
+    let mut buf = Vec::new();
+
+    // ---------- replay: root and pending slots ----------
+    // `rx` already receives everything published from here on, so each write
+    // still to be rooted is in this snapshot, in a live frame, or both.
+    buf.clear();
+    wire::encode_pending_reset(&mut buf);
+    wire::encode_slot(
+        index.slot_with_commitment(Commitment::Finalized),
+        None,
+        Commitment::Finalized,
+        &mut buf,
+    );
+    stream.write_all(&buf).await?;
+    for (slot, parent, confirmed, writes) in index.pending_snapshot() {
+        if parent.is_some() {
+            buf.clear();
+            wire::encode_slot(slot, parent, Commitment::Processed, &mut buf);
+            stream.write_all(&buf).await?;
+        }
+        for (pubkey, acc) in writes {
+            buf.clear();
+            wire::encode_account(&pubkey, &acc, false, &mut buf);
+            stream.write_all(&buf).await?;
+        }
+        if confirmed {
+            buf.clear();
+            wire::encode_slot(slot, None, Commitment::Confirmed, &mut buf);
+            stream.write_all(&buf).await?;
+        }
+    }
+
+    // ---------- replay: rooted state ----------
+    // Live frames are forwarded between shards, so a long replay never lets
+    // `rx` fall behind. A shard snapshot reflects every root published before
+    // it was taken; later roots reach the client through `rx`.
+    let ready = index.is_ready();
+    for shard in 0..index.shard_count() {
+        for (pubkey, acc) in index.shard_snapshot(shard) {
+            buf.clear();
+            wire::encode_account(&pubkey, &acc, true, &mut buf);
+            stream.write_all(&buf).await?;
+        }
+        buf.clear();
+        wire::encode_shard_synced(shard as u32, &mut buf);
+        stream.write_all(&buf).await?;
+        forward_queued(&mut stream, &mut rx).await?;
+    }
+    // While the validator is still streaming its snapshot the marker arrives
+    // live instead.
+    if ready {
+        buf.clear();
+        wire::encode_end_of_startup(&mut buf);
+        stream.write_all(&buf).await?;
+    }
+    stream.flush().await?;
+
+    // ---------- live ----------
+    loop {
+        match rx.recv().await {
+            Ok(frame) => {
+                stream.write_all(&frame).await?;
+                if rx.is_empty() {
+                    stream.flush().await?;
+                }
+            }
+            Err(RecvError::Lagged(n)) => return Err(lagged(n)),
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+/// Write the live frames already queued for the client, without waiting for
+/// more.
+async fn forward_queued<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    rx: &mut broadcast::Receiver<Frame>,
+) -> io::Result<()> {
+    loop {
+        match rx.try_recv() {
+            Ok(frame) => stream.write_all(&frame).await?,
+            Err(TryRecvError::Lagged(n)) => return Err(lagged(n)),
+            // A closed channel ends the live loop that follows.
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+fn lagged(frames: u64) -> io::Error {
+    io::Error::other(format!("client lagged behind by {frames} frames"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::account::Account,
+        tokio::io::{AsyncReadExt, DuplexStream},
+    };
+
+    async fn next_update(stream: &mut DuplexStream) -> Update {
+        let len = stream.read_u32_le().await.unwrap() as usize;
+        let mut frame = vec![0; len];
+        stream.read_exact(&mut frame).await.unwrap();
+        wire::decode(&frame).unwrap()
+    }
+
+    fn account(lamports: u64, slot: u64) -> StoredAccount {
+        let account = Account {
+            lamports,
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        };
+        StoredAccount::new(account, slot, 0)
+    }
+
+    #[tokio::test]
+    async fn replay_covers_pending_then_rooted_state_then_live() {
+        let index = Arc::new(ShardedIndex::default());
+        let (rooted, pending) = (Pubkey::new_unique(), Pubkey::new_unique());
+        index.insert(rooted, account(1, 10));
+        index.finish_startup();
+        index.root_slot(10);
+        index.process_slot(11, Some(10));
+        index.insert_processed(pending, account(2, 11));
+
+        let (tx, _) = broadcast::channel(16);
+        let rx = tx.subscribe();
+        // Published once the client is subscribed: forwarded during the replay.
+        let mut early = Vec::new();
+        wire::encode_slot(11, None, Commitment::Confirmed, &mut early);
+        tx.send(Arc::new(early)).unwrap();
+
+        let (client, server) = tokio::io::duplex(1 << 16);
+        tokio::spawn(serve_client(Box::new(server), rx, index.clone(), 1024));
+        let mut client = client;
+
+        let mut updates = Vec::new();
+        loop {
+            let update = next_update(&mut client).await;
+            let done = matches!(update, Update::EndOfStartup);
+            updates.push(update);
+            if done {
+                break;
+            }
+        }
+        assert!(matches!(updates[0], Update::PendingReset));
+        assert!(matches!(
+            updates[1],
+            Update::Slot {
+                slot: 10,
+                status: Commitment::Finalized,
+                ..
+            }
+        ));
+        assert!(matches!(
+            updates[2],
+            Update::Slot {
+                slot: 11,
+                parent: Some(10),
+                status: Commitment::Processed
+            }
+        ));
+        assert!(matches!(
+            updates[3],
+            Update::Account { pubkey, is_startup: false, .. } if pubkey == pending
+        ));
+        let rooted_at = updates
+            .iter()
+            .position(|u| matches!(u, Update::Account { pubkey, is_startup: true, .. } if *pubkey == rooted))
+            .unwrap();
+        assert!(matches!(updates[rooted_at + 1], Update::ShardSynced { .. }));
+        let synced = updates
+            .iter()
+            .filter(|u| matches!(u, Update::ShardSynced { .. }))
+            .count();
+        assert_eq!(synced, index.shard_count());
+        assert!(updates.iter().any(|u| matches!(
+            u,
+            Update::Slot {
+                slot: 11,
+                status: Commitment::Confirmed,
+                ..
+            }
+        )));
+
+        let mut live = Vec::new();
+        wire::encode_slot(11, None, Commitment::Finalized, &mut live);
+        tx.send(Arc::new(live)).unwrap();
+        assert!(matches!(
+            next_update(&mut client).await,
+            Update::Slot {
+                slot: 11,
+                status: Commitment::Finalized,
+                ..
+            }
+        ));
+    }
+}
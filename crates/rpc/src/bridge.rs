@@ -0,0 +1,241 @@
+//! Ingest client: connects to the `fractal_ingest` Geyser plugin, decodes the
+//! framed update stream (`fractal_shard::wire`) and applies it to the local
+//! `ShardedIndex` and the WebSocket broadcast channel.
+//!
+//! The plugin replays its full state to every new connection, so a dropped
+//! connection is simply re‑established with exponential backoff. The replay
+//! also tells what to forget: pending writes held from the previous
+//! connection are dropped before the pending slots are replayed, and rooted
+//! accounts that a replayed shard no longer holds are dropped once the shard
+//! is complete.
+
+use {
+    super::WsEvent,
+    fractal_shard::{
+        wire::{self, Endpoint, Update, MAX_FRAME_LEN},
+        Commitment, ShardedIndex,
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashSet, io, sync::Arc, time::Duration},
+    tokio::{
+        io::{AsyncRead, AsyncReadExt, BufReader},
+        net::TcpStream,
+        sync::broadcast,
+    },
+};
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// Startup snapshot accounts applied per `insert_startup_batch` call.
+const STARTUP_BATCH: usize = 1024;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Run the ingest client forever.
+pub async fn run(
+    endpoint: Endpoint,
+    index: Arc<ShardedIndex>,
+    txs: Arc<broadcast::Sender<WsEvent>>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match connect(&endpoint).await {
+            Ok(stream) => {
+                tracing::info!("connected to ingest plugin at {:?}", endpoint);
+                backoff = MIN_BACKOFF;
+                match consume(stream, &index, &txs).await {
+                    Ok(()) => tracing::warn!("ingest plugin closed the stream"),
+                    Err(e) => tracing::warn!("ingest stream error: {e}"),
+                }
+            }
+            Err(e) => tracing::warn!("cannot connect to ingest plugin at {:?}: {e}", endpoint),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn connect(endpoint: &Endpoint) -> io::Result<Box<dyn AsyncRead + Send + Unpin>> {
+    match endpoint {
+        Endpoint::Tcp(addr) => {
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        #[cfg(not(unix))]
+        Endpoint::Unix(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        )),
+    }
+}
+
+/// Read frames until EOF (`Ok`) or a transport / decoding error.
+/// Consecutive startup accounts are applied in batches.
+async fn consume<R: AsyncRead + Unpin>(
+    stream: R,
+    index: &ShardedIndex,
+    txs: &broadcast::Sender<WsEvent>,
+) -> io::Result<()> {
+    let mut stream = BufReader::with_capacity(1 << 20, stream);
+    let mut buf = Vec::new();
+    let mut startup = Vec::with_capacity(STARTUP_BATCH);
+    // Keys replayed since the last `ShardSynced`.
+    let mut replayed = HashSet::<Pubkey>::new();
+    loop {
+        // Don't hold a partial batch while waiting for the socket.
+        if !startup.is_empty() && stream.buffer().is_empty() {
+            index.insert_startup_batch(std::mem::take(&mut startup));
+        }
+        let len = match stream.read_u32_le().await {
+            Ok(len) => len as usize,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                index.insert_startup_batch(startup);
+                return Ok(());
+            }
+            Err(e) => {
+                index.insert_startup_batch(startup);
+                return Err(e);
+            }
+        };
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {len} bytes exceeds the limit"),
+            ));
+        }
+        buf.resize(len, 0);
+        stream.read_exact(&mut buf).await?;
+        match wire::decode(&buf)? {
+            Update::Account {
+                pubkey,
+                account,
+                is_startup: true,
+            } => {
+                replayed.insert(pubkey);
+                startup.push((pubkey, account));
+                if startup.len() >= STARTUP_BATCH {
+                    index.insert_startup_batch(std::mem::take(&mut startup));
+                }
+            }
+            update => {
+                // Keep ordering: earlier startup accounts land first.
+                if !startup.is_empty() {
+                    index.insert_startup_batch(std::mem::take(&mut startup));
+                }
+                if let Update::ShardSynced { shard } = update {
+                    let dropped = index.retain_shard(shard as usize, &replayed);
+                    if dropped > 0 {
+                        tracing::info!(
+                            "dropped {dropped} accounts closed upstream from shard {shard}"
+                        );
+                    }
+                    replayed.clear();
+                } else {
+                    apply_update(index, txs, update);
+                }
+            }
+        }
+    }
+}
+
+/// Apply one update to the index and notify WebSocket subscribers.
+pub fn apply_update(index: &ShardedIndex, txs: &broadcast::Sender<WsEvent>, update: Update) {
+    match update {
+        Update::Account {
+            pubkey,
+            account,
+            is_startup,
+        } => {
+            let (slot, lamports) = (account.slot, account.lamports);
+            // Startup / replayed accounts are rooted; live ones wait for their slot.
+            if is_startup {
+                index.insert_startup(pubkey, account);
+            } else if index.insert_processed(pubkey, account) {
+                // No subscribers is not an error.
+                let _ = txs.send(WsEvent::AccountUpdated {
+                    pubkey: pubkey.to_string(),
+                    lamports,
+                    slot,
+                });
+            }
+        }
+        Update::Slot {
+            slot,
+            parent,
+            status,
+        } => match status {
+            Commitment::Processed => index.process_slot(slot, parent),
+            Commitment::Confirmed => index.confirm_slot(slot),
+            Commitment::Finalized => index.root_slot(slot),
+        },
+        Update::EndOfStartup => {
+            tracing::info!(
+                "startup snapshot loaded ({} accounts), building secondary indexes",
+                index.startup_loaded()
+            );
+            index.finish_startup();
+        }
+        Update::PendingReset => index.clear_pending(),
+        // Needs the keys replayed since the previous one; see `consume`.
+        Update::ShardSynced { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, fractal_shard::StoredAccount, solana_sdk::account::Account};
+
+    fn account(lamports: u64, slot: u64) -> StoredAccount {
+        let account = Account {
+            lamports,
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        };
+        StoredAccount::new(account, slot, 0)
+    }
+
+    #[tokio::test]
+    async fn reconnect_replay_forgets_what_upstream_dropped() {
+        let index = ShardedIndex::default();
+        let (txs, _) = broadcast::channel(16);
+        let (kept, closed, pending) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        // State left by a previous connection.
+        index.insert(kept, account(1, 10));
+        index.insert(closed, account(1, 10));
+        index.finish_startup();
+        index.root_slot(10);
+        index.insert_processed(pending, account(1, 11));
+        index.confirm_slot(11);
+
+        // Upstream closed `closed` and abandoned slot 11 while disconnected.
+        let mut replay = Vec::new();
+        wire::encode_pending_reset(&mut replay);
+        wire::encode_slot(12, None, Commitment::Finalized, &mut replay);
+        for shard in 0..index.shard_count() {
+            if index
+                .shard_snapshot(shard)
+                .iter()
+                .any(|(key, _)| *key == kept)
+            {
+                wire::encode_account(&kept, &account(2, 12), true, &mut replay);
+            }
+            wire::encode_shard_synced(shard as u32, &mut replay);
+        }
+        consume(replay.as_slice(), &index, &txs).await.unwrap();
+
+        assert_eq!(index.get(&kept).unwrap().lamports, 2);
+        assert!(index.get(&closed).is_none());
+        assert!(index.get(&pending).is_none());
+        assert!(index.pending_snapshot().is_empty());
+        assert!(index.audit().is_consistent());
+    }
+}
@@ -0,0 +1,322 @@
+//! Account `encoding` / `dataSlice` options shared by the REST routes and
+//! JSON‑RPC methods.
+//!
+//! Accounts are rendered with Solana's own `solana-account-decoder`, so every
+//! encoding (`base58`, `base64`, `base64+zstd`, `jsonParsed`) has the exact
+//! shape Solana RPC produces. `jsonParsed` covers SPL Token / Token‑2022
+//! accounts and mints, nonce, stake, vote, sysvars, address lookup tables and
+//! the upgradeable loader, and falls back to base64 when no parser applies.
+
+use {
+    super::{jsonrpc::INVALID_REQUEST, parse_commitment, RpcError},
+    fractal_shard::{token, Commitment, ShardedIndex, StoredAccount},
+    serde_json::Value,
+    solana_account_decoder::{
+        parse_account_data::AccountAdditionalData, UiAccount, UiAccountData, UiAccountEncoding,
+        UiDataSliceConfig, MAX_BASE58_BYTES,
+    },
+    solana_sdk::pubkey::Pubkey,
+};
+
+/// How the accounts of one call are rendered: encoding, optional data
+/// slice, and the commitment parsers read related accounts (token mints) at.
+pub struct Render<'a> {
+    index: &'a ShardedIndex,
+    encoding: UiAccountEncoding,
+    data_slice: Option<UiDataSliceConfig>,
+    commitment: Commitment,
+}
+
+impl<'a> Render<'a> {
+    /// Validate the options of a call; base64 when `encoding` is omitted.
+    pub fn new(
+        index: &'a ShardedIndex,
+        encoding: Option<&str>,
+        data_slice: Option<UiDataSliceConfig>,
+        commitment: Option<&str>,
+    ) -> Result<Self, RpcError> {
+        let encoding = match encoding {
+            None => UiAccountEncoding::Base64,
+            Some(e) => serde_json::from_value(Value::String(e.to_string()))
+                .map_err(|_| RpcError::invalid_params(format!("unsupported encoding: {e}")))?,
+        };
+        // Same early checks as Solana: parsed data cannot be sliced, and a
+        // slice too long for base58 is rejected before any account is read.
+        match (encoding, data_slice) {
+            (UiAccountEncoding::JsonParsed, Some(_)) => {
+                return Err(RpcError::new(
+                    INVALID_REQUEST,
+                    "Sliced account data can only be encoded using binary (base 58) or base64 encoding.",
+                ));
+            }
+            (UiAccountEncoding::Binary | UiAccountEncoding::Base58, Some(slice))
+                if slice.length > MAX_BASE58_BYTES =>
+            {
+                return Err(RpcError::new(INVALID_REQUEST, base58_too_long()));
+            }
+            _ => {}
+        }
+        Ok(Self {
+            index,
+            encoding,
+            data_slice,
+            commitment: parse_commitment(commitment)?,
+        })
+    }
+
+    /// Render `acc` as Solana's `UiAccount`. Base58 refuses (sliced) data
+    /// over 128 bytes, like Solana.
+    pub fn account(&self, key: &Pubkey, acc: &StoredAccount) -> Result<UiAccount, RpcError> {
+        if let UiAccountEncoding::Binary | UiAccountEncoding::Base58 = self.encoding {
+            let data = slice_data(&acc.data, self.data_slice);
+            if data.len() > MAX_BASE58_BYTES {
+                return Err(RpcError::new(INVALID_REQUEST, base58_too_long()));
+            }
+            // `UiAccount::encode` checks the unsliced length and would put an
+            // error string in place of a short slice of a large account.
+            let data = bs58::encode(data).into_string();
+            return Ok(UiAccount {
+                lamports: acc.lamports,
+                data: match self.encoding {
+                    UiAccountEncoding::Binary => UiAccountData::LegacyBinary(data),
+                    _ => UiAccountData::Binary(data, UiAccountEncoding::Base58),
+                },
+                owner: acc.owner.to_string(),
+                executable: acc.executable,
+                rent_epoch: acc.rent_epoch,
+                space: Some(acc.data.len() as u64),
+            });
+        }
+        let additional_data = match self.encoding {
+            UiAccountEncoding::JsonParsed => self.additional_data(acc),
+            _ => None,
+        };
+        Ok(UiAccount::encode(
+            key,
+            &acc.account,
+            self.encoding,
+            additional_data,
+            self.data_slice,
+        ))
+    }
+
+    /// Token amounts are rendered with the mint's decimals; without a cached
+    /// mint the parser fails and the account falls back to base64, as in
+    /// Solana.
+    fn additional_data(&self, acc: &StoredAccount) -> Option<AccountAdditionalData> {
+        let token_account = token::TokenAccount::unpack(&acc.owner, &acc.data)?;
+        let mint = self
+            .index
+            .get_with_commitment(&token_account.mint, self.commitment)?;
+        let mint = token::Mint::unpack(&mint.owner, &mint.data)?;
+        Some(AccountAdditionalData {
+            spl_token_decimals: Some(mint.decimals),
+        })
+    }
+}
+
+/// The part of `data` a `dataSlice` selects; empty past the end.
+fn slice_data(data: &[u8], data_slice: Option<UiDataSliceConfig>) -> &[u8] {
+    match data_slice {
+        Some(UiDataSliceConfig { offset, length }) => {
+            let start = offset.min(data.len());
+            &data[start..start + length.min(data.len() - start)]
+        }
+        None => data,
+    }
+}
+
+fn base58_too_long() -> String {
+    format!(
+        "Encoded binary (base 58) data should be less than {MAX_BASE58_BYTES} bytes, please use Base64 encoding."
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::account::Account};
+
+    fn stored(owner: Pubkey, data: Vec<u8>) -> StoredAccount {
+        let account = Account {
+            lamports: 1,
+            data,
+            owner,
+            ..Account::default()
+        };
+        StoredAccount::new(account, 1, 0)
+    }
+
+    /// A mint with 6 decimals and a token account of it holding 1_500_000.
+    fn token_pair(index: &ShardedIndex) -> (Pubkey, StoredAccount) {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0u8; token::MINT_LEN];
+        data[44] = 6;
+        data[45] = 1;
+        index.insert(mint, stored(token::TOKEN_PROGRAM_ID, data));
+
+        let mut data = vec![0u8; token::ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(Pubkey::new_unique().as_ref());
+        data[64..72].copy_from_slice(&1_500_000u64.to_le_bytes());
+        data[108] = 1;
+        (mint, stored(token::TOKEN_PROGRAM_ID, data))
+    }
+
+    fn render<'a>(
+        index: &'a ShardedIndex,
+        encoding: &str,
+        data_slice: Option<UiDataSliceConfig>,
+    ) -> Result<Render<'a>, RpcError> {
+        Render::new(index, Some(encoding), data_slice, None)
+    }
+
+    fn slice(offset: usize, length: usize) -> Option<UiDataSliceConfig> {
+        Some(UiDataSliceConfig { offset, length })
+    }
+
+    #[test]
+    fn json_parsed_token_account_uses_mint_decimals() {
+        let index = ShardedIndex::default();
+        let (mint, acc) = token_pair(&index);
+        let key = Pubkey::new_unique();
+        let ui = render(&index, "jsonParsed", None)
+            .unwrap()
+            .account(&key, &acc)
+            .unwrap();
+        let UiAccountData::Json(parsed) = ui.data else {
+            panic!("not parsed: {:?}", ui.data);
+        };
+        assert_eq!(parsed.program, "spl-token");
+        assert_eq!(parsed.space, token::ACCOUNT_LEN as u64);
+        let info = &parsed.parsed["info"];
+        assert_eq!(info["mint"], mint.to_string());
+        assert_eq!(info["tokenAmount"]["amount"], "1500000");
+        assert_eq!(info["tokenAmount"]["decimals"], 6);
+        assert_eq!(info["tokenAmount"]["uiAmountString"], "1.5");
+    }
+
+    #[test]
+    fn json_parsed_falls_back_to_base64() {
+        let index = ShardedIndex::default();
+        let (_, acc) = token_pair(&index);
+        let key = Pubkey::new_unique();
+
+        // Token account whose mint is not cached.
+        let bare = ShardedIndex::default();
+        let render_bare = render(&bare, "jsonParsed", None).unwrap();
+        // Data no parser accepts.
+        let garbage = stored(token::TOKEN_PROGRAM_ID, vec![7; 10]);
+        let render = render(&index, "jsonParsed", None).unwrap();
+
+        for (ui, data) in [
+            (render_bare.account(&key, &acc).unwrap(), &acc.data),
+            (render.account(&key, &garbage).unwrap(), &garbage.data),
+        ] {
+            assert_eq!(
+                ui.data,
+                UiAccountData::Binary(base64::encode(data), UiAccountEncoding::Base64)
+            );
+        }
+    }
+
+    #[test]
+    fn json_parsed_cannot_be_sliced() {
+        let index = ShardedIndex::default();
+        let err = render(&index, "jsonParsed", slice(0, 4)).err().unwrap();
+        assert_eq!(err.code, INVALID_REQUEST);
+        assert_eq!(
+            err.message,
+            "Sliced account data can only be encoded using binary (base 58) or base64 encoding."
+        );
+    }
+
+    #[test]
+    fn base58_and_slices() {
+        let index = ShardedIndex::default();
+        let key = Pubkey::new_unique();
+        let data: Vec<u8> = (0..200).collect();
+        let acc = stored(Pubkey::new_unique(), data.clone());
+
+        let ui = render(&index, "base58", slice(10, 20))
+            .unwrap()
+            .account(&key, &acc)
+            .unwrap();
+        assert_eq!(
+            ui.data,
+            UiAccountData::Binary(
+                bs58::encode(&data[10..30]).into_string(),
+                UiAccountEncoding::Base58
+            )
+        );
+        assert_eq!(ui.space, Some(data.len() as u64));
+
+        // A slice past the end of the data is empty, not an error.
+        let ui = render(&index, "base64", slice(500, 8))
+            .unwrap()
+            .account(&key, &acc)
+            .unwrap();
+        assert_eq!(
+            ui.data,
+            UiAccountData::Binary(String::new(), UiAccountEncoding::Base64)
+        );
+        let ui = render(&index, "base64", slice(190, 50))
+            .unwrap()
+            .account(&key, &acc)
+            .unwrap();
+        assert_eq!(
+            ui.data,
+            UiAccountData::Binary(base64::encode(&data[190..]), UiAccountEncoding::Base64)
+        );
+    }
+
+    #[test]
+    fn base64_zstd_round_trips() {
+        let index = ShardedIndex::default();
+        let data: Vec<u8> = (0..4096).map(|i| (i % 7) as u8).collect();
+        let acc = stored(Pubkey::new_unique(), data.clone());
+        for (data_slice, expected) in [(None, &data[..]), (slice(100, 50), &data[100..150])] {
+            let ui = render(&index, "base64+zstd", data_slice)
+                .unwrap()
+                .account(&Pubkey::new_unique(), &acc)
+                .unwrap();
+            let UiAccountData::Binary(encoded, UiAccountEncoding::Base64Zstd) = ui.data else {
+                panic!("not zstd: {:?}", ui.data);
+            };
+            let compressed = base64::decode(encoded).unwrap();
+            assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn base58_limit_is_an_invalid_request() {
+        let index = ShardedIndex::default();
+        let key = Pubkey::new_unique();
+        let long = stored(Pubkey::new_unique(), vec![1; MAX_BASE58_BYTES + 1]);
+
+        // Checked up front against the requested slice length.
+        for encoding in ["base58", "binary"] {
+            assert!(render(&index, encoding, slice(0, MAX_BASE58_BYTES)).is_ok());
+            let err = render(&index, encoding, slice(0, MAX_BASE58_BYTES + 1))
+                .err()
+                .unwrap();
+            assert_eq!(err.code, INVALID_REQUEST);
+            assert_eq!(err.message, base58_too_long());
+        }
+
+        // Otherwise against the data actually encoded.
+        let base58 = render(&index, "base58", None).unwrap();
+        let err = base58.account(&key, &long).unwrap_err();
+        assert_eq!(err.code, INVALID_REQUEST);
+        assert_eq!(
+            err.message,
+            "Encoded binary (base 58) data should be less than 128 bytes, please use Base64 encoding."
+        );
+        let at_limit = stored(Pubkey::new_unique(), vec![1; MAX_BASE58_BYTES]);
+        assert!(base58.account(&key, &at_limit).is_ok());
+        let sliced = render(&index, "base58", slice(1, MAX_BASE58_BYTES)).unwrap();
+        assert!(sliced.account(&key, &long).is_ok());
+
+        // Base64 has no limit.
+        assert!(render(&index, "base64", slice(0, 10_000)).is_ok());
+    }
+}
@@ -0,0 +1,315 @@
+//! `getProgramAccounts` filters, with the request shape, limits and error
+//! messages of Solana RPC:
+//!
+//! ```text
+//! {"memcmp": {"offset": 0, "bytes": "3Mc6vR", "encoding": "base58"}}
+//! {"dataSize": 165}
+//! "tokenAccountState"
+//! ```
+//!
+//! Memcmp `bytes` are base58 unless `encoding` says `base64`, or a raw byte
+//! array. Filters are verified and decoded once per request into
+//! [`AccountFilter`]s, which are then matched against account data.
+
+use {
+    super::RpcError,
+    fractal_shard::token,
+    serde::{Deserialize, Deserializer},
+    std::fmt,
+};
+
+/// Most filters accepted in one call.
+pub const MAX_GET_PROGRAM_ACCOUNT_FILTERS: usize = 4;
+
+/// Longest memcmp, decoded and encoded.
+const MAX_DATA_SIZE: usize = 128;
+const MAX_DATA_BASE58_SIZE: usize = 175;
+const MAX_DATA_BASE64_SIZE: usize = 172;
+
+/// A filter as sent by the client.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Filter {
+    DataSize(u64),
+    Memcmp(Memcmp),
+    TokenAccountState,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Memcmp {
+    pub offset: usize,
+    #[serde(flatten)]
+    pub bytes: MemcmpEncodedBytes,
+}
+
+/// Memcmp `bytes` together with their `encoding`.
+#[derive(Debug)]
+pub enum MemcmpEncodedBytes {
+    Base58(String),
+    Base64(String),
+    Bytes(Vec<u8>),
+}
+
+impl<'de> Deserialize<'de> for MemcmpEncodedBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum DataType {
+            Encoded(String),
+            Raw(Vec<u8>),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        enum Encoding {
+            Base58,
+            Base64,
+            Bytes,
+        }
+
+        #[derive(Deserialize)]
+        struct Inner {
+            bytes: DataType,
+            encoding: Option<Encoding>,
+        }
+
+        let inner = Inner::deserialize(deserializer)?;
+        Ok(match inner.bytes {
+            DataType::Encoded(bytes) => match inner.encoding.unwrap_or(Encoding::Base58) {
+                Encoding::Base58 | Encoding::Bytes => Self::Base58(bytes),
+                Encoding::Base64 => Self::Base64(bytes),
+            },
+            DataType::Raw(bytes) => Self::Bytes(bytes),
+        })
+    }
+}
+
+/// Why a filter was rejected. Solana reports these in their `Debug` form,
+/// so the variant names are part of the error message.
+pub enum FilterError {
+    DataTooLarge,
+    Base58DecodeError(bs58::decode::Error),
+    Base64DecodeError(base64::DecodeError),
+}
+
+impl fmt::Debug for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DataTooLarge => f.write_str("DataTooLarge"),
+            Self::Base58DecodeError(e) => f.debug_tuple("Base58DecodeError").field(e).finish(),
+            Self::Base64DecodeError(e) => f.debug_tuple("Base64DecodeError").field(e).finish(),
+        }
+    }
+}
+
+/// A verified filter, memcmp bytes decoded.
+#[derive(Debug)]
+pub enum AccountFilter {
+    DataSize(u64),
+    Memcmp { offset: usize, bytes: Vec<u8> },
+    TokenAccountState,
+}
+
+impl AccountFilter {
+    /// `true` if an account holding `data` passes the filter.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(size) => data.len() as u64 == *size,
+            Self::Memcmp { offset, bytes } => offset
+                .checked_add(bytes.len())
+                .and_then(|end| data.get(*offset..end))
+                .is_some_and(|window| window == bytes.as_slice()),
+            Self::TokenAccountState => token::is_token_account_data(data),
+        }
+    }
+}
+
+impl TryFrom<Filter> for AccountFilter {
+    type Error = FilterError;
+
+    fn try_from(filter: Filter) -> Result<Self, FilterError> {
+        let (offset, bytes) = match filter {
+            Filter::DataSize(size) => return Ok(Self::DataSize(size)),
+            Filter::TokenAccountState => return Ok(Self::TokenAccountState),
+            Filter::Memcmp(Memcmp { offset, bytes }) => (offset, bytes),
+        };
+        let bytes = match bytes {
+            MemcmpEncodedBytes::Base58(s) => {
+                if s.len() > MAX_DATA_BASE58_SIZE {
+                    return Err(FilterError::DataTooLarge);
+                }
+                bs58::decode(s)
+                    .into_vec()
+                    .map_err(FilterError::Base58DecodeError)?
+            }
+            MemcmpEncodedBytes::Base64(s) => {
+                if s.len() > MAX_DATA_BASE64_SIZE {
+                    return Err(FilterError::DataTooLarge);
+                }
+                base64::decode(s).map_err(FilterError::Base64DecodeError)?
+            }
+            MemcmpEncodedBytes::Bytes(bytes) => bytes,
+        };
+        if bytes.len() > MAX_DATA_SIZE {
+            return Err(FilterError::DataTooLarge);
+        }
+        Ok(Self::Memcmp { offset, bytes })
+    }
+}
+
+/// Check `filters` against Solana's limits and decode them.
+pub fn verify(filters: Vec<Filter>) -> Result<Vec<AccountFilter>, RpcError> {
+    if filters.len() > MAX_GET_PROGRAM_ACCOUNT_FILTERS {
+        return Err(RpcError::invalid_params(format!(
+            "Too many filters provided; max {MAX_GET_PROGRAM_ACCOUNT_FILTERS}"
+        )));
+    }
+    filters
+        .into_iter()
+        .map(|f| {
+            AccountFilter::try_from(f)
+                .map_err(|e| RpcError::invalid_params(format!("Invalid param: {e:?}")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, serde_json::json};
+
+    fn parse(filters: serde_json::Value) -> Result<Vec<AccountFilter>, RpcError> {
+        verify(serde_json::from_value(filters).unwrap())
+    }
+
+    fn memcmp(
+        offset: usize,
+        bytes: serde_json::Value,
+        encoding: Option<&str>,
+    ) -> serde_json::Value {
+        let mut memcmp = json!({"offset": offset, "bytes": bytes});
+        if let Some(encoding) = encoding {
+            memcmp["encoding"] = json!(encoding);
+        }
+        json!([{ "memcmp": memcmp }])
+    }
+
+    fn decoded(filters: serde_json::Value) -> Vec<u8> {
+        match parse(filters).unwrap().as_slice() {
+            [AccountFilter::Memcmp { bytes, .. }] => bytes.clone(),
+            other => panic!("not a single memcmp: {other:?}"),
+        }
+    }
+
+    fn error(filters: serde_json::Value) -> String {
+        let err = parse(filters).unwrap_err();
+        assert_eq!(err.code, crate::jsonrpc::INVALID_PARAMS);
+        err.message
+    }
+
+    #[test]
+    fn request_shapes() {
+        let filters = parse(json!([
+            {"memcmp": {"offset": 4, "bytes": "2"}},
+            {"dataSize": 165},
+            "tokenAccountState",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            filters.as_slice(),
+            [
+                AccountFilter::Memcmp { offset: 4, bytes },
+                AccountFilter::DataSize(165),
+                AccountFilter::TokenAccountState,
+            ] if bytes == &[1]
+        ));
+        assert!(serde_json::from_value::<Filter>(json!({"dataSize": -1})).is_err());
+        assert!(serde_json::from_value::<Filter>(json!("nope")).is_err());
+    }
+
+    #[test]
+    fn memcmp_bytes_are_base58_by_default() {
+        let bytes = b"fractal".to_vec();
+        let base58 = bs58::encode(&bytes).into_string();
+        assert_eq!(decoded(memcmp(0, json!(base58), None)), bytes);
+        assert_eq!(decoded(memcmp(0, json!(base58), Some("base58"))), bytes);
+        // An `encoding` of `bytes` with a string is base58 too.
+        assert_eq!(decoded(memcmp(0, json!(base58), Some("bytes"))), bytes);
+
+        let base64 = base64::encode(&bytes);
+        assert_eq!(decoded(memcmp(0, json!(base64), Some("base64"))), bytes);
+        assert_eq!(decoded(memcmp(0, json!(bytes), None)), bytes);
+    }
+
+    #[test]
+    fn at_most_four_filters() {
+        let filter = json!({"dataSize": 165});
+        assert_eq!(parse(json!(vec![filter.clone(); 4])).unwrap().len(), 4);
+        assert_eq!(
+            error(json!(vec![filter; 5])),
+            "Too many filters provided; max 4"
+        );
+    }
+
+    #[test]
+    fn memcmp_size_limits() {
+        let too_large = "Invalid param: DataTooLarge";
+
+        // Decoded: 128 bytes.
+        assert_eq!(decoded(memcmp(0, json!(vec![1u8; 128]), None)).len(), 128);
+        assert_eq!(error(memcmp(0, json!(vec![1u8; 129]), None)), too_large);
+        // 129 bytes fit in 172 base64 characters, but not in 128 bytes.
+        let base64 = base64::encode(vec![1u8; 129]);
+        assert_eq!(base64.len(), 172);
+        assert_eq!(error(memcmp(0, json!(base64), Some("base64"))), too_large);
+
+        // Encoded: 175 base58 characters, the longest encoding of 128 bytes.
+        let base58 = bs58::encode([0xff; 128]).into_string();
+        assert_eq!(base58.len(), 175);
+        assert_eq!(decoded(memcmp(0, json!(base58), None)), [0xff; 128]);
+        assert_eq!(
+            error(memcmp(0, json!(format!("2{base58}")), None)),
+            too_large
+        );
+
+        // Encoded: 172 base64 characters.
+        let base64 = base64::encode([0xff; 128]);
+        assert_eq!(base64.len(), 172);
+        assert_eq!(
+            decoded(memcmp(0, json!(base64), Some("base64"))),
+            [0xff; 128]
+        );
+        assert_eq!(
+            error(memcmp(0, json!(format!("{base64}A")), Some("base64"))),
+            too_large
+        );
+    }
+
+    #[test]
+    fn decode_errors_use_solana_messages() {
+        assert_eq!(
+            error(memcmp(0, json!("0OIl"), None)),
+            "Invalid param: Base58DecodeError(InvalidCharacter { character: '0', index: 0 })"
+        );
+        assert!(error(memcmp(0, json!("!!!!"), Some("base64")))
+            .starts_with("Invalid param: Base64DecodeError("));
+    }
+
+    #[test]
+    fn memcmp_out_of_range_never_matches() {
+        let filter = |offset, bytes: &[u8]| AccountFilter::Memcmp {
+            offset,
+            bytes: bytes.to_vec(),
+        };
+        let data = [1, 2, 3, 4];
+        assert!(filter(2, &[3, 4]).matches(&data));
+        assert!(filter(4, &[]).matches(&data));
+        assert!(!filter(3, &[4, 5]).matches(&data));
+        assert!(!filter(4, &[5]).matches(&data));
+        assert!(!filter(5, &[]).matches(&data));
+        assert!(!filter(usize::MAX, &[1]).matches(&data));
+        assert!(!filter(0, &[1]).matches(&[]));
+
+        assert!(AccountFilter::DataSize(4).matches(&data));
+        assert!(!AccountFilter::DataSize(3).matches(&data));
+    }
+}
@@ -0,0 +1,358 @@
+//! Geyser gRPC (Yellowstone‑style) ingest mode, for deployments that cannot
+//! load `fractal_ingest` into the validator.
+//!
+//! Subscribes to account updates (optionally restricted to a list of owner
+//! programs) and slot updates at `processed` commitment and feeds them
+//! through [`bridge::apply_update`](super::bridge::apply_update), so both
+//! ingest modes share one code path. On disconnect the client reconnects
+//! with exponential backoff and asks the server to resume from the last slot
+//! it saw.
+//!
+//! A gRPC stream carries no snapshot (at most the `is_startup` accounts some
+//! servers send first), so the index only reports ready once the first rooted
+//! slot has been applied; until then `/health` keeps failing.
+
+use {
+    super::{bridge::apply_update, WsEvent},
+    fractal_shard::{wire::Update, Commitment, ShardedIndex, StoredAccount},
+    futures::{stream, StreamExt},
+    geyser_grpc::geyser::{
+        geyser_client::GeyserClient, subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus,
+        SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots,
+        SubscribeUpdateAccount, SubscribeUpdateSlot,
+    },
+    solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature},
+    std::{collections::HashMap, sync::Arc, time::Duration},
+    tokio::sync::broadcast,
+    tonic::{
+        metadata::AsciiMetadataValue,
+        service::{interceptor::InterceptedService, Interceptor},
+        transport::{Channel, ClientTlsConfig},
+        Request, Status,
+    },
+};
+
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Connection settings for the gRPC ingest mode.
+#[derive(Clone, Debug)]
+pub struct GrpcConfig {
+    /// `http(s)://host:port` of the Geyser gRPC server.
+    pub endpoint: String,
+    /// Optional `x-token` sent with every request.
+    pub x_token: Option<String>,
+    /// Only stream accounts owned by these programs (all accounts if empty).
+    pub owners: Vec<Pubkey>,
+}
+
+/// Run the gRPC consumer forever.
+pub async fn run(
+    config: GrpcConfig,
+    index: Arc<ShardedIndex>,
+    txs: Arc<broadcast::Sender<WsEvent>>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    let mut last_slot: Option<u64> = None;
+    loop {
+        let before = last_slot;
+        match subscribe(&config, &mut last_slot, &index, &txs).await {
+            Ok(()) => tracing::warn!("geyser gRPC stream ended"),
+            Err(e) => tracing::warn!("geyser gRPC stream error: {e}"),
+        }
+        // Only back off further while no progress is made.
+        if last_slot != before {
+            backoff = MIN_BACKOFF;
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// One subscription, resuming from `last_slot` if set. `last_slot` is kept
+/// up to date so the caller can resume after an error.
+async fn subscribe(
+    config: &GrpcConfig,
+    last_slot: &mut Option<u64>,
+    index: &ShardedIndex,
+    txs: &broadcast::Sender<WsEvent>,
+) -> anyhow::Result<()> {
+    let mut client = connect(config).await?;
+
+    let request = SubscribeRequest {
+        accounts: HashMap::from([(
+            "fractal".to_string(),
+            SubscribeRequestFilterAccounts {
+                owner: config.owners.iter().map(Pubkey::to_string).collect(),
+                ..Default::default()
+            },
+        )]),
+        slots: HashMap::from([(
+            "fractal".to_string(),
+            SubscribeRequestFilterSlots::default(),
+        )]),
+        commitment: Some(CommitmentLevel::Processed as i32),
+        from_slot: *last_slot,
+    };
+    // Keep the request stream open: closing it ends the subscription.
+    let requests = stream::iter([request]).chain(stream::pending());
+    let mut updates = client.subscribe(requests).await?.into_inner();
+    tracing::info!(
+        "subscribed to geyser gRPC at {} (resume from {:?})",
+        config.endpoint,
+        last_slot
+    );
+
+    while let Some(msg) = updates.next().await {
+        let update = match msg?.update_oneof {
+            Some(UpdateOneof::Account(acc)) => account_update(acc),
+            Some(UpdateOneof::Slot(slot)) => {
+                *last_slot = Some(last_slot.map_or(slot.slot, |s| s.max(slot.slot)));
+                slot_update(slot)
+            }
+            _ => None,
+        };
+        if let Some(update) = update {
+            let rooted = matches!(
+                update,
+                Update::Slot {
+                    status: Commitment::Finalized,
+                    ..
+                }
+            );
+            apply_update(index, txs, update);
+            if rooted && !index.is_ready() {
+                tracing::info!("first rooted slot applied, serving from the gRPC stream");
+                index.finish_startup();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the optional `x-token` header to every request.
+#[derive(Clone)]
+struct XToken(Option<AsciiMetadataValue>);
+
+impl Interceptor for XToken {
+    fn call(&mut self, mut req: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.0 {
+            req.metadata_mut().insert("x-token", token.clone());
+        }
+        Ok(req)
+    }
+}
+
+async fn connect(
+    config: &GrpcConfig,
+) -> anyhow::Result<GeyserClient<InterceptedService<Channel, XToken>>> {
+    let mut channel = Channel::from_shared(config.endpoint.clone())?
+        .connect_timeout(Duration::from_secs(10))
+        .tcp_nodelay(true);
+    if config.endpoint.starts_with("https://") {
+        channel = channel.tls_config(ClientTlsConfig::new())?;
+    }
+    let channel = channel.connect().await?;
+
+    let token = config.x_token.as_deref().map(str::parse).transpose()?;
+    Ok(GeyserClient::with_interceptor(channel, XToken(token))
+        .max_decoding_message_size(64 * 1024 * 1024))
+}
+
+fn account_update(msg: SubscribeUpdateAccount) -> Option<Update> {
+    let info = msg.account?;
+    let pubkey = Pubkey::try_from(info.pubkey.as_slice()).ok()?;
+    let owner = Pubkey::try_from(info.owner.as_slice()).ok()?;
+    let txn_signature = info
+        .txn_signature
+        .and_then(|sig| Signature::try_from(sig.as_slice()).ok());
+    Some(Update::Account {
+        pubkey,
+        account: StoredAccount::new(
+            Account {
+                lamports: info.lamports,
+                data: info.data,
+                owner,
+                executable: info.executable,
+                rent_epoch: info.rent_epoch,
+            },
+            msg.slot,
+            info.write_version,
+        )
+        .with_txn_signature(txn_signature),
+        is_startup: msg.is_startup,
+    })
+}
+
+fn slot_update(msg: SubscribeUpdateSlot) -> Option<Update> {
+    let status = match SlotStatus::try_from(msg.status).ok()? {
+        SlotStatus::SlotProcessed => Commitment::Processed,
+        SlotStatus::SlotConfirmed => Commitment::Confirmed,
+        SlotStatus::SlotFinalized => Commitment::Finalized,
+        _ => return None,
+    };
+    Some(Update::Slot {
+        slot: msg.slot,
+        parent: msg.parent,
+        status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures::Stream,
+        geyser_grpc::geyser::{
+            geyser_server::{Geyser, GeyserServer},
+            SubscribeUpdate, SubscribeUpdateAccountInfo,
+        },
+        std::{pin::Pin, sync::Mutex},
+        tokio::{net::TcpListener, sync::mpsc},
+        tonic::{
+            transport::{server::TcpIncoming, Server},
+            Response, Streaming,
+        },
+    };
+
+    /// Serves one subscription: reports the request and streams what the
+    /// test feeds it.
+    struct MockGeyser {
+        requests: mpsc::UnboundedSender<SubscribeRequest>,
+        updates: Mutex<Option<mpsc::UnboundedReceiver<SubscribeUpdate>>>,
+    }
+
+    #[tonic::async_trait]
+    impl Geyser for MockGeyser {
+        type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+        async fn subscribe(
+            &self,
+            request: Request<Streaming<SubscribeRequest>>,
+        ) -> Result<Response<Self::SubscribeStream>, Status> {
+            let mut requests = request.into_inner();
+            if let Some(req) = requests.message().await? {
+                let _ = self.requests.send(req);
+            }
+            let updates = self
+                .updates
+                .lock()
+                .unwrap()
+                .take()
+                .ok_or_else(|| Status::unavailable("one subscription only"))?;
+            Ok(Response::new(Box::pin(stream::unfold(
+                updates,
+                |mut updates| async move { updates.recv().await.map(|u| (Ok(u), updates)) },
+            ))))
+        }
+    }
+
+    fn slot(slot: u64, parent: Option<u64>, status: SlotStatus) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["fractal".into()],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent,
+                status: status as i32,
+                dead_error: None,
+            })),
+        }
+    }
+
+    fn account(pubkey: &Pubkey, owner: &Pubkey, lamports: u64, slot: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["fractal".into()],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    lamports,
+                    owner: owner.to_bytes().to_vec(),
+                    data: vec![7; 8],
+                    write_version: 1,
+                    ..Default::default()
+                }),
+                slot,
+                is_startup: false,
+            })),
+        }
+    }
+
+    /// Wait until `done` holds, or fail after a few seconds.
+    async fn eventually(done: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn subscribes_and_applies_accounts_and_slots() {
+        let (requests_tx, mut requests) = mpsc::unbounded_channel();
+        let (updates, updates_rx) = mpsc::unbounded_channel();
+        let mock = MockGeyser {
+            requests: requests_tx,
+            updates: Mutex::new(Some(updates_rx)),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(GeyserServer::new(mock))
+                .serve_with_incoming(incoming),
+        );
+
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = GrpcConfig {
+            endpoint: format!("http://{addr}"),
+            x_token: None,
+            owners: vec![owner],
+        };
+        let index = Arc::new(ShardedIndex::default());
+        let (txs, _) = broadcast::channel(16);
+        let client = {
+            let index = index.clone();
+            tokio::spawn(async move {
+                let mut last_slot = Some(99);
+                subscribe(&config, &mut last_slot, &index, &txs)
+                    .await
+                    .map(|()| last_slot)
+            })
+        };
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.accounts["fractal"].owner, [owner.to_string()]);
+        assert_eq!(request.commitment, Some(CommitmentLevel::Processed as i32));
+        assert_eq!(request.from_slot, Some(99));
+
+        updates
+            .send(slot(100, Some(99), SlotStatus::SlotProcessed))
+            .unwrap();
+        updates.send(account(&key, &owner, 5, 100)).unwrap();
+        eventually(|| {
+            index
+                .get_with_commitment(&key, Commitment::Processed)
+                .is_some()
+        })
+        .await;
+        assert!(index.get(&key).is_none());
+        assert!(!index.is_ready());
+
+        updates
+            .send(slot(100, None, SlotStatus::SlotConfirmed))
+            .unwrap();
+        updates
+            .send(slot(100, None, SlotStatus::SlotFinalized))
+            .unwrap();
+        eventually(|| index.is_ready()).await;
+        assert_eq!(index.get(&key).unwrap().lamports, 5);
+        assert_eq!(index.slot_with_commitment(Commitment::Finalized), 100);
+        assert_eq!(index.get_program_accounts(&owner).len(), 1);
+
+        drop(updates);
+        assert_eq!(client.await.unwrap().unwrap(), Some(100));
+    }
+}
@@ -0,0 +1,884 @@
+//! JSON‑RPC 2.0 front door (`POST /`).
+//!
+//! Stock Solana clients (web3.js, `solana-client`, Anchor) speak the standard
+//! `{"jsonrpc","id","method","params"}` envelope. This module parses that
+//! envelope, maps the positional `params` onto the request structs used by the
+//! REST routes, runs the shared handler bodies and wraps the result in
+//! Solana's `{"context":{"slot"},"value"}` shape.
+//!
+//! A JSON array of request objects is a batch: every call is spawned on its
+//! own task so independent look‑ups hit the index in parallel, and the
+//! responses are returned in request order.
+//!
+//! A request without an `id` is a notification: it is run, but gets no
+//! response. A call or batch made only of notifications is answered with an
+//! empty `204 No Content`.
+
+use {
+    super::{
+        account_info, check_api_key,
+        encoding::Render,
+        filter::Filter,
+        largest_token_accounts, multiple_accounts, parse_commitment, program_accounts,
+        program_accounts_page, slot,
+        token_account_balance, token_accounts_by_delegate, token_accounts_by_owner, token_supply,
+        AppState, GetAccountInfoReq, GetLargestTokenAccountsReq, GetMultipleAccountsReq,
+        GetProgramAccountsPageReq, GetProgramAccountsReq, GetSlotReq, GetTokenAccountBalanceReq,
+        GetTokenAccountsByDelegateReq, GetTokenAccountsByOwnerReq, GetTokenSupplyReq,
+        REQUEST_COUNT, REQUEST_DURATION,
+    },
+    axum::{
+        body::Bytes,
+        extract::Extension,
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+        Json,
+    },
+    fractal_shard::StoredAccount,
+    futures::future::join_all,
+    serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize},
+    serde_json::{json, Value},
+    solana_account_decoder::{UiAccount, UiDataSliceConfig},
+    solana_sdk::pubkey::Pubkey,
+    std::time::Instant,
+};
+
+// ---------- Solana / JSON‑RPC error codes ----------
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+pub const NODE_UNHEALTHY: i64 = -32005;
+pub const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
+/// Error object returned in the `error` member of a JSON‑RPC response. The
+/// shared handler bodies also return it so the REST routes can map it onto an
+/// HTTP status.
+#[derive(Serialize, Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn parse_error() -> Self {
+        Self::new(PARSE_ERROR, "Parse error")
+    }
+
+    pub fn invalid_request() -> Self {
+        Self::new(INVALID_REQUEST, "Invalid request")
+    }
+
+    pub fn method_not_found() -> Self {
+        Self::new(METHOD_NOT_FOUND, "Method not found")
+    }
+
+    pub fn batch_too_large(len: usize, max: usize) -> Self {
+        Self::new(
+            INVALID_REQUEST,
+            format!("Invalid request: batch of {len} calls exceeds the limit of {max}"),
+        )
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(INTERNAL_ERROR, message)
+    }
+
+    /// The startup snapshot is still being indexed, so a walk would miss
+    /// accounts.
+    pub fn node_unhealthy() -> Self {
+        Self::new(NODE_UNHEALTHY, "Node is unhealthy: loading snapshot")
+    }
+
+    pub fn min_context_slot_not_reached() -> Self {
+        Self::new(
+            MIN_CONTEXT_SLOT_NOT_REACHED,
+            "Minimum context slot has not been reached",
+        )
+    }
+}
+
+impl From<RpcError> for (StatusCode, String) {
+    fn from(e: RpcError) -> Self {
+        let status = match e.code {
+            PARSE_ERROR | INVALID_REQUEST | INVALID_PARAMS => StatusCode::BAD_REQUEST,
+            METHOD_NOT_FOUND => StatusCode::NOT_FOUND,
+            NODE_UNHEALTHY | MIN_CONTEXT_SLOT_NOT_REACHED => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.message)
+    }
+}
+
+// ---------- Envelope ----------
+#[derive(Deserialize)]
+struct JsonRpcRequest {
+    jsonrpc: String,
+    /// `None` for a notification. An explicit `null` is still an id.
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Deserialize a member that is present, even as `null`, to `Some`; a
+/// missing one takes the `#[serde(default)]` of `None`.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize)]
+struct JsonRpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Value,
+}
+
+impl JsonRpcResponse {
+    fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(v) => (Some(v), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+// ---------- Solana response shapes ----------
+#[derive(Serialize)]
+struct RpcContext {
+    slot: u64,
+}
+
+#[derive(Serialize)]
+struct RpcResponse<T> {
+    context: RpcContext,
+    value: T,
+}
+
+#[derive(Serialize)]
+struct RpcKeyedAccount {
+    pubkey: String,
+    account: UiAccount,
+}
+
+impl RpcKeyedAccount {
+    fn new(render: &Render, key: &Pubkey, acc: &StoredAccount) -> Result<Self, RpcError> {
+        Ok(Self {
+            pubkey: key.to_string(),
+            account: render.account(key, acc)?,
+        })
+    }
+}
+
+// ---------- Method configs ----------
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RpcAccountInfoConfig {
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+impl RpcAccountInfoConfig {
+    fn render<'a>(&self, state: &'a AppState) -> Result<Render<'a>, RpcError> {
+        Render::new(
+            &state.index,
+            self.encoding.as_deref(),
+            self.data_slice,
+            self.commitment.as_deref(),
+        )
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RpcProgramAccountsConfig {
+    #[serde(flatten)]
+    account_config: RpcAccountInfoConfig,
+    #[serde(default)]
+    filters: Option<Vec<Filter>>,
+    #[serde(default)]
+    with_context: Option<bool>,
+}
+
+/// Config of `getProgramAccountsPage`: `getProgramAccounts` options plus the
+/// page size and the cursor of the previous page.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RpcProgramAccountsPageConfig {
+    #[serde(flatten)]
+    account_config: RpcAccountInfoConfig,
+    #[serde(default)]
+    filters: Option<Vec<Filter>>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcProgramAccountsPage {
+    accounts: Vec<RpcKeyedAccount>,
+    cursor: Option<String>,
+    min_context_slot: u64,
+}
+
+/// Second positional argument of `getTokenAccountsByOwner` and
+/// `getTokenAccountsByDelegate`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum RpcTokenAccountsFilter {
+    Mint(String),
+    ProgramId(String),
+}
+
+/// Positional `params` reader: takes arguments from the front of the array
+/// and reports Solana‑style `Invalid params` errors.
+struct Params(std::vec::IntoIter<Value>);
+
+impl Params {
+    fn new(params: Value) -> Result<Self, RpcError> {
+        match params {
+            Value::Array(v) => Ok(Self(v.into_iter())),
+            Value::Null => Ok(Self(Vec::new().into_iter())),
+            _ => Err(RpcError::invalid_params("Invalid params: expected array")),
+        }
+    }
+
+    fn required<T: DeserializeOwned>(&mut self, name: &str) -> Result<T, RpcError> {
+        let v = self
+            .0
+            .next()
+            .ok_or_else(|| RpcError::invalid_params(format!("missing `{name}` argument")))?;
+        serde_json::from_value(v)
+            .map_err(|e| RpcError::invalid_params(format!("Invalid params: {e}")))
+    }
+
+    fn optional<T: DeserializeOwned + Default>(&mut self) -> Result<T, RpcError> {
+        match self.0.next() {
+            None | Some(Value::Null) => Ok(T::default()),
+            Some(v) => serde_json::from_value(v)
+                .map_err(|e| RpcError::invalid_params(format!("Invalid params: {e}"))),
+        }
+    }
+}
+
+/// Context of a response. Taken after the read: the slot tracker moves before
+/// a write lands, so a slot read later is never older than the data returned.
+fn context(state: &AppState, commitment: Option<&str>) -> Result<RpcContext, RpcError> {
+    let commitment = parse_commitment(commitment)?;
+    Ok(RpcContext {
+        slot: state.index.slot_with_commitment(commitment),
+    })
+}
+
+fn to_value<T: Serialize>(v: T) -> Result<Value, RpcError> {
+    serde_json::to_value(v).map_err(|e| RpcError::internal(e.to_string()))
+}
+
+// ---------------------------------------------------------------------------
+// POST /
+// ---------------------------------------------------------------------------
+pub async fn rpc_handler(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+
+    let resp = match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Array(calls)) => return Ok(handle_batch(&state, calls).await),
+        Ok(v) => handle_call(&state, v).await,
+        Err(_) => Some(JsonRpcResponse::new(Value::Null, Err(RpcError::parse_error()))),
+    };
+
+    Ok(match resp {
+        Some(resp) => Json(resp).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+/// Run a batch concurrently. An empty or oversized batch is rejected as a
+/// whole with a single error object, as the JSON‑RPC spec prescribes.
+/// Notifications are left out of the response array.
+async fn handle_batch(state: &AppState, calls: Vec<Value>) -> Response {
+    if calls.is_empty() {
+        return Json(JsonRpcResponse::new(
+            Value::Null,
+            Err(RpcError::invalid_request()),
+        ))
+        .into_response();
+    }
+    if calls.len() > state.max_batch_size {
+        return Json(JsonRpcResponse::new(
+            Value::Null,
+            Err(RpcError::batch_too_large(calls.len(), state.max_batch_size)),
+        ))
+        .into_response();
+    }
+
+    let tasks = calls.into_iter().map(|call| {
+        let state = state.clone();
+        tokio::spawn(async move { handle_call(&state, call).await })
+    });
+    let responses: Vec<JsonRpcResponse> = join_all(tasks)
+        .await
+        .into_iter()
+        .filter_map(|joined| {
+            joined.unwrap_or_else(|e| {
+                Some(JsonRpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::internal(e.to_string())),
+                ))
+            })
+        })
+        .collect();
+
+    if responses.is_empty() {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    Json(responses).into_response()
+}
+
+/// Validate one request object and run it. `None` for a notification.
+async fn handle_call(state: &AppState, raw: Value) -> Option<JsonRpcResponse> {
+    let req: JsonRpcRequest = match serde_json::from_value(raw) {
+        Ok(r) => r,
+        Err(_) => {
+            return Some(JsonRpcResponse::new(
+                Value::Null,
+                Err(RpcError::invalid_request()),
+            ))
+        }
+    };
+    if req.jsonrpc != "2.0" {
+        let id = req.id.unwrap_or(Value::Null);
+        return Some(JsonRpcResponse::new(id, Err(RpcError::invalid_request())));
+    }
+
+    let start = Instant::now();
+    let id = req.id.clone().unwrap_or(Value::Null);
+    let outcome = dispatch(state, &req.method, req.params, &id).await;
+
+    // Unknown method names are not recorded so clients cannot blow up the
+    // label cardinality.
+    if !matches!(&outcome, Err(e) if e.code == METHOD_NOT_FOUND) {
+        let status = match &outcome {
+            Ok(_) => "200".to_string(),
+            Err(e) => e.code.to_string(),
+        };
+        REQUEST_DURATION
+            .with_label_values(&[req.method.as_str()])
+            .observe(start.elapsed().as_secs_f64());
+        REQUEST_COUNT
+            .with_label_values(&[req.method.as_str(), status.as_str()])
+            .inc();
+    }
+
+    req.id.map(|id| JsonRpcResponse::new(id, outcome))
+}
+
+async fn dispatch(
+    state: &AppState,
+    method: &str,
+    params: Value,
+    id: &Value,
+) -> Result<Value, RpcError> {
+    match method {
+        "getAccountInfo" => rpc_get_account_info(state, params),
+        "getMultipleAccounts" => rpc_get_multiple_accounts(state, params),
+        "getProgramAccounts" => rpc_get_program_accounts(state, params),
+        "getProgramAccountsPage" => rpc_get_program_accounts_page(state, params),
+        "getTokenAccountsByOwner" => rpc_get_token_accounts_by_owner(state, params),
+        "getTokenAccountsByDelegate" => rpc_get_token_accounts_by_delegate(state, params),
+        "getTokenAccountBalance" => rpc_get_token_account_balance(state, params),
+        "getTokenSupply" => rpc_get_token_supply(state, params),
+        "getTokenLargestAccounts" => rpc_get_token_largest_accounts(state, params),
+        "getSlot" => rpc_get_slot(state, params),
+        "simulateTransaction" => rpc_simulate_transaction(state, params, id).await,
+        _ => Err(RpcError::method_not_found()),
+    }
+}
+
+// ---------- Methods ----------
+fn rpc_get_account_info(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let pubkey: String = p.required("pubkey")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    let render = config.render(state)?;
+
+    let (pk, acc) = account_info(
+        state,
+        &GetAccountInfoReq {
+            pubkey,
+            encoding: config.encoding,
+            data_slice: config.data_slice,
+            commitment: config.commitment.clone(),
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse {
+        context,
+        value: acc.map(|acc| render.account(&pk, &acc)).transpose()?,
+    })
+}
+
+fn rpc_get_multiple_accounts(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let pubkeys: Vec<String> = p.required("pubkeys")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    let render = config.render(state)?;
+
+    let accounts = multiple_accounts(
+        state,
+        &GetMultipleAccountsReq {
+            pubkeys,
+            encoding: config.encoding,
+            data_slice: config.data_slice,
+            commitment: config.commitment.clone(),
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse {
+        context,
+        value: accounts
+            .iter()
+            .map(|(pk, acc)| acc.as_ref().map(|acc| render.account(pk, acc)).transpose())
+            .collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+fn rpc_get_program_accounts(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let program: String = p.required("program id")?;
+    let config: RpcProgramAccountsConfig = p.optional()?;
+    let render = config.account_config.render(state)?;
+
+    let commitment = config.account_config.commitment;
+    let accounts = program_accounts(
+        state,
+        GetProgramAccountsReq {
+            program,
+            limit: None,
+            offset: None,
+            filters: config.filters,
+            commitment: commitment.clone(),
+            encoding: None,
+            data_slice: None,
+            stream: None,
+        },
+    )?;
+    let context = context(state, commitment.as_deref())?;
+    let value = accounts
+        .iter()
+        .map(|(k, acc)| RpcKeyedAccount::new(&render, k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Solana returns a bare array unless `withContext` is set.
+    if config.with_context.unwrap_or(false) {
+        to_value(RpcResponse { context, value })
+    } else {
+        to_value(value)
+    }
+}
+
+fn rpc_get_program_accounts_page(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let program: String = p.required("program id")?;
+    let config: RpcProgramAccountsPageConfig = p.optional()?;
+    let render = config.account_config.render(state)?;
+
+    let commitment = config.account_config.commitment;
+    let page = program_accounts_page(
+        state,
+        GetProgramAccountsPageReq {
+            program,
+            cursor: config.cursor,
+            limit: config.limit,
+            filters: config.filters,
+            commitment: commitment.clone(),
+            encoding: None,
+            data_slice: None,
+        },
+    )?;
+    let context = context(state, commitment.as_deref())?;
+    let accounts = page
+        .accounts
+        .iter()
+        .map(|(k, acc)| RpcKeyedAccount::new(&render, k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    to_value(RpcResponse {
+        context,
+        value: RpcProgramAccountsPage {
+            accounts,
+            cursor: page.cursor,
+            min_context_slot: page.min_context_slot,
+        },
+    })
+}
+
+fn rpc_get_token_accounts_by_owner(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let owner: String = p.required("owner")?;
+    let filter: RpcTokenAccountsFilter = p.required("mint or programId")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    let render = config.render(state)?;
+
+    let (mint, program_id) = match filter {
+        RpcTokenAccountsFilter::Mint(m) => (Some(m), None),
+        RpcTokenAccountsFilter::ProgramId(p) => (None, Some(p)),
+    };
+    let accounts = token_accounts_by_owner(
+        state,
+        GetTokenAccountsByOwnerReq {
+            owner,
+            mint,
+            program_id,
+            limit: None,
+            offset: None,
+            commitment: config.commitment.clone(),
+            encoding: None,
+            data_slice: None,
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse {
+        context,
+        value: accounts
+            .iter()
+            .map(|(k, acc)| RpcKeyedAccount::new(&render, k, acc))
+            .collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+fn rpc_get_token_accounts_by_delegate(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let delegate: String = p.required("delegate")?;
+    let filter: RpcTokenAccountsFilter = p.required("mint or programId")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    let render = config.render(state)?;
+
+    let (mint, program_id) = match filter {
+        RpcTokenAccountsFilter::Mint(m) => (Some(m), None),
+        RpcTokenAccountsFilter::ProgramId(p) => (None, Some(p)),
+    };
+    let accounts = token_accounts_by_delegate(
+        state,
+        GetTokenAccountsByDelegateReq {
+            delegate,
+            mint,
+            program_id,
+            limit: None,
+            offset: None,
+            commitment: config.commitment.clone(),
+            encoding: None,
+            data_slice: None,
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse {
+        context,
+        value: accounts
+            .iter()
+            .map(|(k, acc)| RpcKeyedAccount::new(&render, k, acc))
+            .collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+fn rpc_get_token_account_balance(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let pubkey: String = p.required("pubkey")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+
+    let value = token_account_balance(
+        state,
+        &GetTokenAccountBalanceReq {
+            pubkey,
+            commitment: config.commitment.clone(),
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse { context, value })
+}
+
+fn rpc_get_token_supply(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let mint: String = p.required("mint")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+
+    let value = token_supply(
+        state,
+        &GetTokenSupplyReq {
+            mint,
+            commitment: config.commitment.clone(),
+        },
+    )?;
+
+    let context = context(state, config.commitment.as_deref())?;
+    to_value(RpcResponse { context, value })
+}
+
+/// Ranked from rooted state, so the context is always the finalized slot.
+fn rpc_get_token_largest_accounts(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let mint: String = p.required("mint")?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    parse_commitment(config.commitment.as_deref())?;
+
+    let value = largest_token_accounts(state, &GetLargestTokenAccountsReq { mint, limit: None })?;
+
+    let context = context(state, None)?;
+    to_value(RpcResponse { context, value })
+}
+
+fn rpc_get_slot(state: &AppState, params: Value) -> Result<Value, RpcError> {
+    let mut p = Params::new(params)?;
+    let config: RpcAccountInfoConfig = p.optional()?;
+    to_value(slot(
+        state,
+        &GetSlotReq {
+            commitment: config.commitment,
+        },
+    )?)
+}
+
+/// `simulateTransaction` is forwarded as a complete JSON‑RPC call to the
+/// downstream validator and its `result`/`error` passed back unchanged.
+async fn rpc_simulate_transaction(
+    state: &AppState,
+    params: Value,
+    id: &Value,
+) -> Result<Value, RpcError> {
+    let client = reqwest::Client::new();
+    let resp: Value = client
+        .post(&state.downstream_rpc)
+        .json(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "simulateTransaction",
+            "params": params,
+        }))
+        .send()
+        .await
+        .map_err(|e| RpcError::internal(format!("downstream error: {e}")))?
+        .json()
+        .await
+        .map_err(|e| RpcError::internal(format!("downstream read error: {e}")))?;
+
+    if let Some(err) = resp.get("error") {
+        return Err(RpcError::new(
+            err.get("code")
+                .and_then(Value::as_i64)
+                .unwrap_or(INTERNAL_ERROR),
+            err.get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("downstream error"),
+        ));
+    }
+    Ok(resp.get("result").cloned().unwrap_or(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, fractal_shard::ShardedIndex, std::sync::Arc};
+
+    async fn call(body: &str) -> (StatusCode, Option<Value>) {
+        call_on(Arc::new(ShardedIndex::default()), body).await
+    }
+
+    async fn call_on(index: Arc<ShardedIndex>, body: &str) -> (StatusCode, Option<Value>) {
+        let state = AppState::for_tests(index);
+        let resp = rpc_handler(Extension(state), HeaderMap::new(), Bytes::from(body.to_owned()))
+            .await
+            .unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, (!body.is_empty()).then(|| serde_json::from_slice(&body).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn notification_gets_no_response() {
+        let (status, body) = call(r#"{"jsonrpc":"2.0","method":"getSlot"}"#).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(body, None);
+    }
+
+    #[tokio::test]
+    async fn null_id_is_not_a_notification() {
+        let (status, body) = call(r#"{"jsonrpc":"2.0","id":null,"method":"getSlot"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, Some(json!({"jsonrpc": "2.0", "result": 0, "id": null})));
+    }
+
+    #[tokio::test]
+    async fn batch_of_notifications_gets_no_content() {
+        let (status, body) = call(
+            r#"[{"jsonrpc":"2.0","method":"getSlot"},{"jsonrpc":"2.0","method":"nope"}]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(body, None);
+    }
+
+    #[tokio::test]
+    async fn batch_answers_only_calls_with_an_id() {
+        let (status, body) = call(
+            r#"[
+                {"jsonrpc":"2.0","id":1,"method":"getSlot"},
+                {"jsonrpc":"2.0","method":"getSlot"},
+                {"jsonrpc":"2.0","id":"b","method":"nope"},
+                {"foo":"bar"}
+            ]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = body.unwrap();
+        let ids: Vec<&Value> = body.as_array().unwrap().iter().map(|r| &r["id"]).collect();
+        assert_eq!(ids, [&json!(1), &json!("b"), &Value::Null]);
+        assert_eq!(body[1]["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(body[2]["error"]["code"], json!(INVALID_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn empty_batch_is_a_single_invalid_request() {
+        let (status, body) = call("[]").await;
+        assert_eq!(status, StatusCode::OK);
+        let body = body.unwrap();
+        assert!(body.is_object());
+        assert_eq!(body["id"], Value::Null);
+        assert_eq!(body["error"]["code"], json!(INVALID_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn oversized_batch_is_rejected_as_a_whole() {
+        let calls = vec![r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#; 101];
+        let (status, body) = call(&format!("[{}]", calls.join(","))).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = body.unwrap();
+        assert!(body.is_object());
+        assert_eq!(body["id"], Value::Null);
+        assert_eq!(body["error"]["code"], json!(INVALID_REQUEST));
+        assert_eq!(
+            body["error"]["message"],
+            json!("Invalid request: batch of 101 calls exceeds the limit of 100")
+        );
+
+        let calls = vec![r#"{"jsonrpc":"2.0","id":1,"method":"getSlot"}"#; 100];
+        let (_, body) = call(&format!("[{}]", calls.join(","))).await;
+        assert_eq!(body.unwrap().as_array().unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn mixed_batch_keeps_request_order() {
+        let (status, body) = call(
+            r#"[
+                1,
+                {"jsonrpc":"2.0","id":1,"method":"getSlot"},
+                {"jsonrpc":"1.0","id":2,"method":"getSlot"},
+                {"jsonrpc":"2.0","method":"nope"},
+                {"jsonrpc":"2.0","id":3,"method":"getAccountInfo","params":["bad"]},
+                {"jsonrpc":"2.0","method":"getSlot"},
+                {"jsonrpc":"2.0","id":4,"method":"getSlot","params":[{"commitment":"processed"}]}
+            ]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = body.unwrap();
+        let body = body.as_array().unwrap();
+        assert_eq!(body.len(), 5);
+
+        assert_eq!(body[0]["id"], Value::Null);
+        assert_eq!(body[0]["error"]["code"], json!(INVALID_REQUEST));
+        assert_eq!(body[1], json!({"jsonrpc": "2.0", "result": 0, "id": 1}));
+        assert_eq!(body[2]["id"], json!(2));
+        assert_eq!(body[2]["error"]["code"], json!(INVALID_REQUEST));
+        assert_eq!(body[3]["id"], json!(3));
+        assert_eq!(body[3]["error"]["code"], json!(INVALID_PARAMS));
+        assert_eq!(body[4], json!({"jsonrpc": "2.0", "result": 0, "id": 4}));
+    }
+
+    #[tokio::test]
+    async fn account_info_context_is_not_older_than_the_account() {
+        let index = Arc::new(ShardedIndex::default());
+        index.finish_startup();
+        let key = Pubkey::new_unique();
+        let account = solana_sdk::account::Account {
+            lamports: 1,
+            ..Default::default()
+        };
+        index.insert_processed(key, StoredAccount::new(account.clone(), 5, 0));
+        index.root_slot(5);
+        index.insert_processed(key, StoredAccount::new(account, 9, 0));
+
+        for (commitment, slot) in [("finalized", 5), ("processed", 9)] {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"getAccountInfo",
+                    "params":["{key}",{{"commitment":"{commitment}"}}]}}"#
+            );
+            let (_, body) = call_on(index.clone(), &body).await;
+            let result = &body.unwrap()["result"];
+            assert!(!result["value"].is_null(), "{commitment}");
+            assert!(result["context"]["slot"].as_u64().unwrap() >= slot, "{commitment}");
+        }
+    }
+
+    #[tokio::test]
+    async fn token_largest_accounts_returns_at_most_twenty() {
+        let index = Arc::new(ShardedIndex::default());
+        let mint = Pubkey::new_unique();
+        crate::tests::token_holders(&index, mint, 25);
+        index.finish_startup();
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getTokenLargestAccounts","params":["{mint}"]}}"#
+        );
+        let (status, body) = call_on(index, &body).await;
+        assert_eq!(status, StatusCode::OK);
+        let value = &body.unwrap()["result"]["value"];
+        assert_eq!(value.as_array().unwrap().len(), 20);
+        assert_eq!(value[0]["amount"], json!("25"));
+    }
+
+    #[tokio::test]
+    async fn program_accounts_page_is_refused_while_loading() {
+        let program = Pubkey::new_unique();
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getProgramAccountsPage","params":["{program}"]}}"#
+        );
+        let (_, body) = call(&body).await;
+        assert_eq!(body.unwrap()["error"]["code"], json!(NODE_UNHEALTHY));
+    }
+}
@@ -7,6 +7,8 @@
 
 use {
     axum::{
+        body::Bytes,
+        error_handling::HandleErrorLayer,
         extract::{
             ws::{Message, WebSocket, WebSocketUpgrade},
             Extension, Json, Query,
@@ -17,25 +19,42 @@ use {
         Router,
     },
     clap::Parser,
-    fractal_shard::ShardedIndex,
+    fractal_shard::{
+        token, Commitment, IndexEntry, ShardedIndex, SlotTracker, StoredAccount,
+    },
+    futures::{SinkExt, StreamExt},
     prometheus::{
-        Encoder, TextEncoder, register_histogram, register_int_counter_vec,
-        register_int_gauge, Histogram, IntCounterVec, IntGauge,
+        Encoder, TextEncoder, register_histogram_vec, register_int_counter_vec,
+        register_int_gauge, HistogramVec, IntCounterVec, IntGauge,
     },
-    redis::AsyncCommands,
     serde::{Deserialize, Serialize},
-    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    solana_account_decoder::{UiAccountData, UiAccountEncoding, UiDataSliceConfig},
+    solana_sdk::{hash::hashv, pubkey::Pubkey},
     std::{
         net::SocketAddr,
         sync::Arc,
-        time::Instant,
+        time::{Duration, Instant},
     },
     tokio::sync::broadcast,
-    tower::limit::RateLimitLayer,
+    tower::{BoxError, ServiceBuilder},
     tower_http::cors::{Any, CorsLayer},
     tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt},
 };
 
+mod bridge;
+mod encoding;
+mod filter;
+mod grpc;
+mod jsonrpc;
+mod stream;
+
+use {
+    encoding::Render,
+    filter::{AccountFilter, Filter},
+    jsonrpc::RpcError,
+    stream::{GetProgramAccountsBinaryReq, StreamFormat},
+};
+
 /// CLI arguments – mainly for the optional Redis URL and downstream validator RPC.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -51,11 +70,56 @@ struct Args {
     /// Optional API key that must be sent in the `x-api-key` header.
     #[arg(long, env = "API_KEY")]
     api_key: Option<String>,
+
+    /// Address of the `fractal_ingest` Geyser plugin stream (`host:port` or
+    /// `unix:<path>`). Without it the index is never populated.
+    #[arg(long, env = "INGEST_ENDPOINT", conflicts_with = "grpc_endpoint")]
+    ingest_endpoint: Option<String>,
+
+    /// Geyser gRPC endpoint (`http(s)://host:port`) to ingest from instead of
+    /// the plugin stream.
+    #[arg(long, env = "GRPC_ENDPOINT")]
+    grpc_endpoint: Option<String>,
+
+    /// Optional `x-token` for the Geyser gRPC endpoint.
+    #[arg(long, env = "GRPC_X_TOKEN")]
+    grpc_x_token: Option<String>,
+
+    /// Only ingest accounts owned by these programs in gRPC mode
+    /// (comma‑separated; all accounts if omitted).
+    #[arg(long, env = "GRPC_OWNERS", value_delimiter = ',')]
+    grpc_owners: Vec<String>,
+
+    /// Maximum number of calls accepted in a single JSON‑RPC batch.
+    #[arg(long, env = "MAX_BATCH_SIZE", default_value_t = 100)]
+    max_batch_size: usize,
+
+    /// Memcmp windows to index, as `<program>:<offset>:<length>`
+    /// (comma‑separated), e.g. `<program>:0:8` for Anchor discriminators.
+    /// `getProgramAccounts` memcmp filters on exactly such a window skip the
+    /// scan of the program.
+    #[arg(long, env = "MEMCMP_INDEXES", value_delimiter = ',')]
+    memcmp_index: Vec<String>,
+}
+
+/// Parse a `--memcmp-index` entry.
+fn parse_memcmp_index(spec: &str) -> anyhow::Result<(Pubkey, usize, usize)> {
+    let mut parts = spec.split(':');
+    let (Some(program), Some(offset), Some(length), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("invalid --memcmp-index {spec:?}: expected <program>:<offset>:<length>");
+    };
+    let program = Pubkey::try_from(program)
+        .map_err(|_| anyhow::anyhow!("invalid program in --memcmp-index {spec:?}"))?;
+    let length: usize = length.parse()?;
+    anyhow::ensure!(length > 0, "empty window in --memcmp-index {spec:?}");
+    Ok((program, offset.parse()?, length))
 }
 
 // ---------- Prometheus metrics ----------
 lazy_static::lazy_static! {
-    static ref REQUEST_DURATION: Histogram = register_histogram!(
+    static ref REQUEST_DURATION: HistogramVec = register_histogram_vec!(
         "rpc_request_seconds",
         "RPC latency (seconds)",
         &["handler"]
@@ -74,6 +138,13 @@ lazy_static::lazy_static! {
         "Number of accounts currently cached"
     )
     .unwrap();
+
+    static ref PROGRAM_ACCOUNTS_PLAN: IntCounterVec = register_int_counter_vec!(
+        "rpc_program_accounts_plan_total",
+        "getProgramAccounts queries by access path (memcmp index or program scan)",
+        &["plan"]
+    )
+    .unwrap();
 }
 
 // ---------- Request / response structs ----------
@@ -85,29 +156,107 @@ struct GetProgramAccountsReq {
     #[serde(default)]
     offset: Option<usize>,
     #[serde(default)]
-    filters: Option<Vec<Filter>>, // memcmp / datasize filters
-}
-
-#[derive(Deserialize)]
-#[serde(tag = "memcmp", rename_all = "camelCase")]
-enum Filter {
-    Memcmp {
-        offset: usize,
-        bytes: String, // base64
-    },
-    DataSize {
-        size: usize,
-    },
+    filters: Option<Vec<Filter>>, // memcmp / dataSize / tokenAccountState
+    #[serde(default)]
+    commitment: Option<String>, // processed / confirmed / finalized (default)
+    #[serde(default)]
+    encoding: Option<String>, // base58 / base64 (default) / base64+zstd / jsonParsed
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+    #[serde(default)]
+    stream: Option<StreamFormat>, // json / ndjson: send accounts as they are read
 }
 
 #[derive(Serialize)]
 struct AccountResp {
     pubkey: String,
     lamports: u64,
-    data: String, // base64
+    data: AccountData,
     owner: String,
     executable: bool,
     rent_epoch: u64,
+    slot: u64, // slot the account was last written at
+}
+
+/// `data` of a REST account: a plain base64 string unless an `encoding` was
+/// requested, in which case it takes Solana's shape for that encoding.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum AccountData {
+    Base64(String),
+    Encoded(UiAccountData),
+}
+
+impl AccountResp {
+    /// An account with `data` rendered by `render`. Unless `encoding` was
+    /// given explicitly, base64 stays a plain string.
+    fn encoded(
+        render: &Render,
+        encoding: Option<&str>,
+        key: &Pubkey,
+        acc: &StoredAccount,
+    ) -> Result<Self, RpcError> {
+        let data = match render.account(key, acc)?.data {
+            UiAccountData::Binary(data, UiAccountEncoding::Base64) if encoding.is_none() => {
+                AccountData::Base64(data)
+            }
+            data => AccountData::Encoded(data),
+        };
+        Ok(Self::with_data(key, acc, data))
+    }
+
+    fn with_data(key: &Pubkey, acc: &StoredAccount, data: AccountData) -> Self {
+        Self {
+            pubkey: key.to_string(),
+            lamports: acc.lamports,
+            data,
+            owner: acc.owner.to_string(),
+            executable: acc.executable,
+            rent_epoch: acc.rent_epoch,
+            slot: acc.slot,
+        }
+    }
+}
+
+/// Token amount in Solana's `UiTokenAmount` shape.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UiTokenAmount {
+    amount: String, // base units, as a string to keep u64 precision
+    decimals: u8,
+    ui_amount: Option<f64>,
+    ui_amount_string: String,
+}
+
+impl UiTokenAmount {
+    fn new(amount: u64, decimals: u8) -> Self {
+        Self {
+            amount: amount.to_string(),
+            decimals,
+            ui_amount: Some(amount as f64 / 10f64.powi(decimals as i32)),
+            ui_amount_string: ui_amount_string(amount, decimals),
+        }
+    }
+}
+
+/// `amount` shifted by `decimals` with trailing zeros trimmed, e.g.
+/// `(1_500_000, 6)` → `"1.5"` (Solana's `real_number_string_trimmed`).
+fn ui_amount_string(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return amount.to_string();
+    }
+    let mut s = format!("{:0>width$}", amount, width = decimals + 1);
+    s.insert(s.len() - decimals, '.');
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// One entry of `getTokenLargestAccounts`.
+#[derive(Serialize)]
+struct TokenAccountBalance {
+    address: String,
+    #[serde(flatten)]
+    amount: UiTokenAmount,
 }
 
 #[derive(Deserialize)]
@@ -128,6 +277,7 @@ enum WsEvent {
     },
     SlotUpdated {
         slot: u64,
+        commitment: &'static str,
     },
 }
 
@@ -138,8 +288,21 @@ struct AppState {
     txs: Arc<broadcast::Sender<WsEvent>>,
     api_key: Option<String>,
     downstream_rpc: String,
-    #[cfg(feature = "distributed")]
-    redis: Option<redis::Client>,
+    max_batch_size: usize,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State around `index`, without API key or downstream RPC.
+    fn for_tests(index: Arc<ShardedIndex>) -> Self {
+        Self {
+            index,
+            txs: Arc::new(broadcast::channel(16).0),
+            api_key: None,
+            downstream_rpc: String::new(),
+            max_batch_size: 100,
+        }
+    }
 }
 
 // ---------- Main ----------
@@ -159,46 +322,86 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     // ---------- shared state ----------
-    let index = Arc::new(ShardedIndex::default());
-
+    #[allow(unused_mut)]
+    let mut index = ShardedIndex::default();
     #[cfg(feature = "distributed")]
     if let Some(ref url) = args.redis_url {
-        // Enable Redis backing.
-        let client = redis::Client::open(url.clone())?;
         // Tell the index to use Redis for get/insert.
-        let mut_index = Arc::get_mut(&mut Arc::clone(&index)).unwrap();
-        mut_index.enable_redis(url)?;
+        index.enable_redis(url)?;
         tracing::info!("Redis distributed cache enabled: {}", url);
     }
+    let index = Arc::new(index);
+    for spec in &args.memcmp_index {
+        let (program, offset, length) = parse_memcmp_index(spec)?;
+        index.register_memcmp_index(program, offset, length);
+        tracing::info!("memcmp index on {program} at {offset}..{}", offset + length);
+    }
 
     let (tx, _rx) = broadcast::channel::<WsEvent>(8192);
+    let txs = Arc::new(tx);
+    slot_notifier(&index.slots(), txs.clone());
+
+    if let Some(ref endpoint) = args.ingest_endpoint {
+        let endpoint = endpoint.parse().map_err(anyhow::Error::msg)?;
+        tokio::spawn(bridge::run(endpoint, index.clone(), txs.clone()));
+    } else if let Some(ref endpoint) = args.grpc_endpoint {
+        let owners = args
+            .grpc_owners
+            .iter()
+            .map(|s| Pubkey::try_from(s.as_str()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| anyhow::anyhow!("invalid pubkey in --grpc-owners"))?;
+        let config = grpc::GrpcConfig {
+            endpoint: endpoint.clone(),
+            x_token: args.grpc_x_token.clone(),
+            owners,
+        };
+        tokio::spawn(grpc::run(config, index.clone(), txs.clone()));
+    } else {
+        tracing::warn!(
+            "neither --ingest-endpoint nor --grpc-endpoint configured; the account cache stays empty"
+        );
+    }
+
     let state = AppState {
         index: index.clone(),
-        txs: Arc::new(tx),
+        txs,
         api_key: args.api_key.clone(),
         downstream_rpc: args.downstream_rpc.clone(),
-        #[cfg(feature = "distributed")]
-        redis: args
-            .redis_url
-            .as_ref()
-            .map(|url| redis::Client::open(url.clone()).unwrap()),
+        max_batch_size: args.max_batch_size,
     };
 
     // ---------- router ----------
-    let rate_limiter = RateLimitLayer::new(3000, std::time::Duration::from_secs(1));
+    // `RateLimit` is not `Clone`, so it sits behind a buffer; requests over
+    // the limit wait in the buffer, and errors of the stack become 503s.
+    let rate_limiter = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(|e: BoxError| async move {
+            (StatusCode::SERVICE_UNAVAILABLE, e.to_string())
+        }))
+        .buffer(4096)
+        .rate_limit(3000, Duration::from_secs(1));
     let cors = CorsLayer::new()
         .allow_origin(Any) // replace with a whitelist in production
         .allow_methods(Any)
         .allow_headers(Any);
 
     let app = Router::new()
+        .route("/", post(jsonrpc::rpc_handler))
         .route("/health", get(health_handler))
         .route("/metrics", get(metrics_handler))
+        .route("/audit", get(audit_handler))
         .route("/getProgramAccounts", post(get_program_accounts))
+        .route("/getProgramAccountsPage", post(get_program_accounts_page))
+        .route("/getProgramAccountsBinary", post(get_program_accounts_binary))
         .route("/getMultipleAccounts", post(get_multiple_accounts))
         .route("/getAccountInfo", post(get_account_info))
         .route("/getTokenAccountsByOwner", post(get_token_accounts_by_owner))
+        .route("/getTokenAccountsByDelegate", post(get_token_accounts_by_delegate))
         .route("/getLargestTokenAccounts", post(get_largest_token_accounts))
+        .route("/getTokenLargestAccounts", post(get_largest_token_accounts))
+        .route("/getTokenAccountBalance", post(get_token_account_balance))
+        .route("/getTokenSupply", post(get_token_supply))
+        .route("/getSlot", get(get_slot).post(get_slot))
         .route("/simulateTransaction", post(simulate_transaction))
         .route("/ws", get(websocket_handler))
         .layer(rate_limiter)
@@ -207,7 +410,7 @@ async fn main() -> anyhow::Result<()> {
 
     // ---------- serve ----------
     let addr: SocketAddr = "0.0.0.0:8899".parse()?;
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
+    let listener = tokio::net::TcpListener::bind(addr).await?;
 
     // Graceful shutdown on SIGTERM / Ctrl‑C
     let shutdown_signal = async {
@@ -217,13 +420,28 @@ async fn main() -> anyhow::Result<()> {
     };
 
     tracing::info!("Fractal RPC listening on {}", addr);
-    server
+    axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal)
         .await?;
 
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Slot notifier: turns slot advances into `WsEvent::SlotUpdated`
+// ---------------------------------------------------------------------------
+/// Broadcast every slot advance as it is applied, so subscribers see each
+/// slot of each commitment level in order.
+fn slot_notifier(slots: &SlotTracker, txs: Arc<broadcast::Sender<WsEvent>>) {
+    slots.on_advance(move |slot, commitment| {
+        // No subscribers is not an error.
+        let _ = txs.send(WsEvent::SlotUpdated {
+            slot,
+            commitment: commitment.as_str(),
+        });
+    });
+}
+
 // ---------------------------------------------------------------------------
 // Helper: API‑key validation (optional)
 // ---------------------------------------------------------------------------
@@ -238,6 +456,17 @@ fn check_api_key(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCod
     }
 }
 
+// ---------------------------------------------------------------------------
+// Helper: commitment parameter (defaults to finalized, like Solana)
+// ---------------------------------------------------------------------------
+fn parse_commitment(commitment: Option<&str>) -> Result<Commitment, RpcError> {
+    commitment
+        .map(str::parse)
+        .transpose()
+        .map_err(RpcError::invalid_params)
+        .map(Option::unwrap_or_default)
+}
+
 // ---------------------------------------------------------------------------
 // Handlers
 // ---------------------------------------------------------------------------
@@ -245,11 +474,19 @@ fn check_api_key(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCod
 async fn health_handler(
     Extension(state): Extension<AppState>,
 ) -> impl IntoResponse {
-    // The cache is considered healthy when it contains at least one account.
-    if state.index.len() > 0 {
-        (StatusCode::OK, "ok")
+    // The cache is healthy once the startup snapshot has been fully loaded;
+    // until then report progress so load balancers keep traffic away.
+    if state.index.is_ready() {
+        (StatusCode::OK, "ok".to_string())
     } else {
-        (StatusCode::SERVICE_UNAVAILABLE, "cache empty")
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "loading snapshot: {} accounts loaded, {} cached",
+                state.index.startup_loaded(),
+                state.index.len()
+            ),
+        )
     }
 }
 
@@ -259,11 +496,103 @@ async fn metrics_handler() -> impl IntoResponse {
     let mut buf = Vec::new();
     encoder.encode(&metric_families, &mut buf).unwrap();
     (
-        [("Content-Type", encoder.format_type())],
+        [("Content-Type", encoder.format_type().to_string())],
         String::from_utf8(buf).unwrap(),
     )
 }
 
+// ---------------------------------------------------------------------------
+// GET /audit
+// ---------------------------------------------------------------------------
+/// Divergences listed per kind in an audit response; the rest are counted.
+const AUDIT_SAMPLE: usize = 100;
+
+#[derive(Serialize)]
+struct AuditResp {
+    accounts: usize,
+    consistent: bool,
+    missing: usize,
+    stale: usize,
+    missing_sample: Vec<AuditEntry>,
+    stale_sample: Vec<AuditEntry>,
+}
+
+/// One secondary‑index entry: `key` listed under `group` in `index`.
+#[derive(Serialize)]
+struct AuditEntry {
+    index: &'static str,
+    group: String,
+    key: String,
+    /// Amount ranked at, or `offset:base58 value` of a memcmp window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl AuditEntry {
+    fn new(entry: &IndexEntry) -> Self {
+        let (index, group, key, detail) = match entry {
+            IndexEntry::Owner { owner, key } => ("owner", owner, key, None),
+            IndexEntry::Wallet { wallet, key } => ("wallet", wallet, key, None),
+            IndexEntry::Mint { mint, key } => ("mint", mint, key, None),
+            IndexEntry::Delegate { delegate, key } => ("delegate", delegate, key, None),
+            IndexEntry::Amount { mint, amount, key } => {
+                ("amount", mint, key, Some(amount.to_string()))
+            }
+            IndexEntry::Memcmp {
+                program,
+                offset,
+                value,
+                key,
+            } => (
+                "memcmp",
+                program,
+                key,
+                Some(format!("{offset}:{}", bs58::encode(value).into_string())),
+            ),
+        };
+        Self {
+            index,
+            group: group.to_string(),
+            key: key.to_string(),
+            detail,
+        }
+    }
+}
+
+/// Check every secondary index against the primary shards. Walks the whole
+/// index on a blocking thread; meant for operators, not clients.
+async fn audit_handler(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<AuditResp>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let audit = tokio::task::spawn_blocking(move || state.index.audit())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !audit.is_consistent() {
+        tracing::warn!(
+            "index audit: {} missing and {} stale secondary entries",
+            audit.missing.len(),
+            audit.stale.len()
+        );
+    }
+    let sample = |entries: &[IndexEntry]| {
+        entries
+            .iter()
+            .take(AUDIT_SAMPLE)
+            .map(AuditEntry::new)
+            .collect()
+    };
+    Ok(Json(AuditResp {
+        accounts: audit.accounts,
+        consistent: audit.is_consistent(),
+        missing: audit.missing.len(),
+        stale: audit.stale.len(),
+        missing_sample: sample(&audit.missing),
+        stale_sample: sample(&audit.stale),
+    }))
+}
+
 // ---------------------------------------------------------------------------
 // GET /getProgramAccounts
 // ---------------------------------------------------------------------------
@@ -271,25 +600,80 @@ async fn get_program_accounts(
     Extension(state): Extension<AppState>,
     headers: HeaderMap,
     Json(req): Json<GetProgramAccountsReq>,
-) -> Result<Json<Vec<AccountResp>>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     // ---------- API‑key ----------
     check_api_key(&state, &headers)?;
 
+    // ---------- streamed: bounded memory, latency recorded by the walk ----------
+    if let Some(format) = req.stream {
+        let resp = stream::program_accounts(state, req, format)?;
+        REQUEST_COUNT
+            .with_label_values(&["getProgramAccounts", "200"])
+            .inc();
+        return Ok(resp);
+    }
+
     let start = Instant::now();
 
-    // ---------- parse program pubkey ----------
-    let program = Pubkey::try_from(req.program.as_str())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid program pubkey".into()))?;
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let encoding = req.encoding.clone();
+    let accounts = program_accounts(&state, req)?;
 
-    // ---------- fetch accounts ----------
-    let mut accounts = state.index.get_program_accounts(&program);
+    // ---------- transform to response ----------
+    let out = accounts
+        .iter()
+        .map(|(k, acc)| AccountResp::encoded(&render, encoding.as_deref(), k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    // ---------- apply optional filters ----------
-    if let Some(filters) = req.filters {
-        accounts = apply_filters(accounts, filters);
-    }
+    // ---------- metrics ----------
+    let elapsed = start.elapsed().as_secs_f64();
+    REQUEST_DURATION
+        .with_label_values(&["getProgramAccounts"])
+        .observe(elapsed);
+    REQUEST_COUNT
+        .with_label_values(&["getProgramAccounts", "200"])
+        .inc();
+    CACHE_SIZE.set(state.index.len() as i64);
 
-    // ---------- pagination ----------
+    Ok(Json(out).into_response())
+}
+
+/// Shared body of `getProgramAccounts` (REST route and JSON‑RPC method).
+fn program_accounts(
+    state: &AppState,
+    req: GetProgramAccountsReq,
+) -> Result<Vec<(Pubkey, Arc<StoredAccount>)>, RpcError> {
+    // ---------- parse program pubkey ----------
+    let program = Pubkey::try_from(req.program.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid program pubkey"))?;
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+
+    let filters = filter::verify(req.filters.unwrap_or_default())?;
+
+    // ---------- fetch accounts: memcmp index if one covers a filter ----------
+    let indexed = indexed_memcmp(state, &program, &filters).and_then(|(offset, bytes)| {
+        state
+            .index
+            .get_program_accounts_by_memcmp(&program, offset, bytes, commitment)
+    });
+    let plan = if indexed.is_some() { "index" } else { "scan" };
+    PROGRAM_ACCOUNTS_PLAN.with_label_values(&[plan]).inc();
+    let mut accounts = indexed.unwrap_or_else(|| {
+        state
+            .index
+            .get_program_accounts_with_commitment(&program, commitment)
+    });
+
+    // ---------- apply filters (the indexed one holds already) ----------
+    accounts.retain(|(_, acc)| filters.iter().all(|f| f.matches(&acc.data)));
+
+    // ---------- pagination (key order, so `offset` is deterministic) ----------
+    accounts.sort_unstable_by_key(|(k, _)| *k);
     if let Some(offset) = req.offset {
         if offset < accounts.len() {
             accounts.drain(0..offset);
@@ -301,55 +685,243 @@ async fn get_program_accounts(
         accounts.truncate(limit);
     }
 
-    // ---------- transform to response ----------
-    let out: Vec<AccountResp> = accounts
-        .into_iter()
-        .map(|(k, acc)| AccountResp {
-            pubkey: k.to_string(),
-            lamports: acc.lamports,
-            data: base64::encode(&acc.data),
-            owner: acc.owner.to_string(),
-            executable: acc.executable,
-            rent_epoch: acc.rent_epoch,
+    Ok(accounts)
+}
+
+/// The memcmp filter to run a query through, if `program` has an index on its
+/// window.
+fn indexed_memcmp<'a>(
+    state: &AppState,
+    program: &Pubkey,
+    filters: &'a [AccountFilter],
+) -> Option<(usize, &'a [u8])> {
+    let windows = state.index.memcmp_indexes(program);
+    filters.iter().find_map(|f| match f {
+        AccountFilter::Memcmp { offset, bytes } if windows.contains(&(*offset, bytes.len())) => {
+            Some((*offset, bytes.as_slice()))
+        }
+        _ => None,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// GET /getProgramAccountsPage
+// ---------------------------------------------------------------------------
+
+/// Accounts per page when `limit` is omitted, and the most a page may hold.
+const DEFAULT_PAGE_LIMIT: usize = 1_000;
+const MAX_PAGE_LIMIT: usize = 10_000;
+
+#[derive(Deserialize)]
+struct GetProgramAccountsPageReq {
+    program: String,
+    #[serde(default)]
+    cursor: Option<String>, // from the previous page; omit to start a walk
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    filters: Option<Vec<Filter>>,
+    #[serde(default)]
+    commitment: Option<String>,
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+}
+
+#[derive(Serialize)]
+struct ProgramAccountsPage {
+    accounts: Vec<AccountResp>,
+    /// Pass back to get the next page; `None` once the walk is complete.
+    cursor: Option<String>,
+    /// Slot the walk started at: a lower bound on the slot of every page,
+    /// not the slot they are read at. Later pages can show writes made after
+    /// the first one.
+    min_context_slot: u64,
+}
+
+/// Position of a page walk, handed to clients as an opaque base58 string:
+/// the last key returned, the slot the walk started at (a node behind it
+/// refuses the next page), and a digest of what it walks so the cursor
+/// cannot continue a different walk.
+struct Cursor {
+    after: Pubkey,
+    min_context_slot: u64,
+    walk: [u8; 8],
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let mut buf = [0u8; 48];
+        buf[..32].copy_from_slice(self.after.as_ref());
+        buf[32..40].copy_from_slice(&self.min_context_slot.to_le_bytes());
+        buf[40..].copy_from_slice(&self.walk);
+        bs58::encode(buf).into_string()
+    }
+
+    /// Decode `cursor`, checking it was handed out for the walk `walk`.
+    fn decode(cursor: &str, walk: [u8; 8]) -> Result<Self, RpcError> {
+        let buf: [u8; 48] = bs58::decode(cursor)
+            .into_vec()
+            .ok()
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| RpcError::invalid_params("invalid cursor"))?;
+        if buf[40..] != walk {
+            return Err(RpcError::invalid_params(
+                "cursor belongs to a walk with another program, filters or commitment",
+            ));
+        }
+        Ok(Self {
+            after: Pubkey::new_from_array(buf[..32].try_into().unwrap()),
+            min_context_slot: u64::from_le_bytes(buf[32..40].try_into().unwrap()),
+            walk,
         })
-        .collect();
+    }
+
+    /// Digest of the program, filters and commitment of a walk.
+    fn walk(program: &Pubkey, filters: &[AccountFilter], commitment: Commitment) -> [u8; 8] {
+        let mut parts = vec![program.as_ref().to_vec(), commitment.as_str().as_bytes().to_vec()];
+        for filter in filters {
+            parts.push(match filter {
+                AccountFilter::DataSize(size) => [&[0][..], &size.to_le_bytes()].concat(),
+                AccountFilter::Memcmp { offset, bytes } => {
+                    [&[1][..], &(*offset as u64).to_le_bytes(), bytes].concat()
+                }
+                AccountFilter::TokenAccountState => vec![2],
+            });
+        }
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        hashv(&parts).to_bytes()[..8].try_into().unwrap()
+    }
+}
+
+/// One page of accounts, before rendering.
+struct Page {
+    accounts: Vec<(Pubkey, Arc<StoredAccount>)>,
+    cursor: Option<String>,
+    min_context_slot: u64,
+}
+
+async fn get_program_accounts_page(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<GetProgramAccountsPageReq>,
+) -> Result<Json<ProgramAccountsPage>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let start = Instant::now();
+
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let encoding = req.encoding.clone();
+    let page = program_accounts_page(&state, req)?;
+    let accounts = page
+        .accounts
+        .iter()
+        .map(|(k, acc)| AccountResp::encoded(&render, encoding.as_deref(), k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    // ---------- metrics ----------
-    let elapsed = start.elapsed().as_secs_f64();
     REQUEST_DURATION
-        .with_label_values(&["getProgramAccounts"])
-        .observe(elapsed);
+        .with_label_values(&["getProgramAccountsPage"])
+        .observe(start.elapsed().as_secs_f64());
     REQUEST_COUNT
-        .with_label_values(&["getProgramAccounts", "200"])
+        .with_label_values(&["getProgramAccountsPage", "200"])
         .inc();
-    CACHE_SIZE.set(state.index.len() as i64);
 
-    Ok(Json(out))
+    Ok(Json(ProgramAccountsPage {
+        accounts,
+        cursor: page.cursor,
+        min_context_slot: page.min_context_slot,
+    }))
+}
+
+/// Shared body of `getProgramAccountsPage` (REST route and JSON‑RPC method).
+/// Walking every page returns each account that exists for the whole walk
+/// exactly once, in key order, even while writes continue; accounts created
+/// or closed mid-walk may be missed (see
+/// [`ShardedIndex::get_program_accounts_page`]). Refused until the startup
+/// snapshot is indexed, since an empty page would read as a finished walk.
+fn program_accounts_page(
+    state: &AppState,
+    req: GetProgramAccountsPageReq,
+) -> Result<Page, RpcError> {
+    let program = Pubkey::try_from(req.program.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid program pubkey"))?;
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let filters = filter::verify(req.filters.unwrap_or_default())?;
+    let limit = req.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if !(1..=MAX_PAGE_LIMIT).contains(&limit) {
+        return Err(RpcError::invalid_params(format!(
+            "limit must be between 1 and {MAX_PAGE_LIMIT}"
+        )));
+    }
+
+    if !state.index.is_ready() {
+        return Err(RpcError::node_unhealthy());
+    }
+
+    let walk = Cursor::walk(&program, &filters, commitment);
+    let slot = state.index.slot_with_commitment(commitment);
+    let (after, min_context_slot) = match req.cursor.as_deref() {
+        Some(cursor) => {
+            let cursor = Cursor::decode(cursor, walk)?;
+            (Some(cursor.after), cursor.min_context_slot)
+        }
+        None => (None, slot),
+    };
+    // A node behind the walk (another replica, or restarted since the last
+    // page) would serve older state than the pages already returned.
+    if slot < min_context_slot {
+        return Err(RpcError::min_context_slot_not_reached());
+    }
+
+    let memcmp = indexed_memcmp(state, &program, &filters);
+    let plan = if memcmp.is_some() { "index" } else { "scan" };
+    PROGRAM_ACCOUNTS_PLAN.with_label_values(&[plan]).inc();
+    let overlay = state.index.pending_overlay(&program, commitment);
+    let accounts = state.index.get_program_accounts_page(
+        &program,
+        memcmp,
+        &overlay,
+        after.as_ref(),
+        limit,
+        |acc| filters.iter().all(|f| f.matches(&acc.data)),
+    );
+
+    let cursor = (accounts.len() == limit).then(|| {
+        Cursor {
+            after: accounts[limit - 1].0,
+            min_context_slot,
+            walk,
+        }
+        .encode()
+    });
+    Ok(Page {
+        accounts,
+        cursor,
+        min_context_slot,
+    })
 }
 
 // ---------------------------------------------------------------------------
-// Helper: apply memcmp / datasize filters (very similar to Solana's own logic)
+// GET /getProgramAccountsBinary
 // ---------------------------------------------------------------------------
-fn apply_filters(
-    accounts: Vec<(Pubkey, Arc<solana_sdk::account::Account>)>,
-    filters: Vec<Filter>,
-) -> Vec<(Pubkey, Arc<solana_sdk::account::Account>)> {
-    accounts
-        .into_iter()
-        .filter(|(_, acc)| {
-            filters.iter().all(|f| match f {
-                Filter::Memcmp { offset, bytes } => {
-                    let decoded = base64::decode(bytes).unwrap_or_default();
-                    if *offset + decoded.len() > acc.data.len() {
-                        false
-                    } else {
-                        acc.data[*offset..*offset + decoded.len()] == decoded[..]
-                    }
-                }
-                Filter::DataSize { size } => acc.data.len() == *size,
-            })
-        })
-        .collect()
+async fn get_program_accounts_binary(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<GetProgramAccountsBinaryReq>,
+) -> Result<Response, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    // Streamed like `getProgramAccounts` with `stream`; the walk records the
+    // latency.
+    let resp = stream::program_accounts_binary(state, req)?;
+    REQUEST_COUNT
+        .with_label_values(&["getProgramAccountsBinary", "200"])
+        .inc();
+    Ok(resp)
 }
 
 // ---------------------------------------------------------------------------
@@ -359,7 +931,11 @@ fn apply_filters(
 struct GetMultipleAccountsReq {
     pubkeys: Vec<String>,
     #[serde(default)]
-    encoding: Option<String>, // base64, base58, jsonParsed (we only support base64)
+    encoding: Option<String>, // base58 / base64 (default) / base64+zstd / jsonParsed
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+    #[serde(default)]
+    commitment: Option<String>,
 }
 
 async fn get_multiple_accounts(
@@ -370,23 +946,20 @@ async fn get_multiple_accounts(
     check_api_key(&state, &headers)?;
     let start = Instant::now();
 
-    let mut out = Vec::with_capacity(req.pubkeys.len());
-    for pk_str in &req.pubkeys {
-        let pk = Pubkey::try_from(pk_str.as_str())
-            .map_err(|_| (StatusCode::BAD_REQUEST, "invalid pubkey".into()))?;
-        if let Some(acc) = state.index.get(&pk) {
-            out.push(Some(AccountResp {
-                pubkey: pk.to_string(),
-                lamports: acc.lamports,
-                data: base64::encode(&acc.data),
-                owner: acc.owner.to_string(),
-                executable: acc.executable,
-                rent_epoch: acc.rent_epoch,
-            }));
-        } else {
-            out.push(None);
-        }
-    }
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let out = multiple_accounts(&state, &req)?
+        .iter()
+        .map(|(pk, acc)| {
+            acc.as_ref()
+                .map(|acc| AccountResp::encoded(&render, req.encoding.as_deref(), pk, acc))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
     // metrics
     let elapsed = start.elapsed().as_secs_f64();
@@ -400,6 +973,25 @@ async fn get_multiple_accounts(
     Ok(Json(out))
 }
 
+/// Requested keys with their account, if cached.
+type MaybeAccounts = Vec<(Pubkey, Option<Arc<StoredAccount>>)>;
+
+/// Shared body of `getMultipleAccounts`. Missing accounts are kept as `None`
+/// so the output lines up with the requested keys.
+fn multiple_accounts(
+    state: &AppState,
+    req: &GetMultipleAccountsReq,
+) -> Result<MaybeAccounts, RpcError> {
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let mut out = Vec::with_capacity(req.pubkeys.len());
+    for pk_str in &req.pubkeys {
+        let pk = Pubkey::try_from(pk_str.as_str())
+            .map_err(|_| RpcError::invalid_params("invalid pubkey"))?;
+        out.push((pk, state.index.get_with_commitment(&pk, commitment)));
+    }
+    Ok(out)
+}
+
 // ---------------------------------------------------------------------------
 // GET /getAccountInfo
 // ---------------------------------------------------------------------------
@@ -408,6 +1000,10 @@ struct GetAccountInfoReq {
     pubkey: String,
     #[serde(default)]
     encoding: Option<String>,
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+    #[serde(default)]
+    commitment: Option<String>,
 }
 
 async fn get_account_info(
@@ -418,19 +1014,16 @@ async fn get_account_info(
     check_api_key(&state, &headers)?;
     let start = Instant::now();
 
-    let pk = Pubkey::try_from(req.pubkey.as_str())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid pubkey".into()))?;
-    let resp = state
-        .index
-        .get(&pk)
-        .map(|acc| AccountResp {
-            pubkey: pk.to_string(),
-            lamports: acc.lamports,
-            data: base64::encode(&acc.data),
-            owner: acc.owner.to_string(),
-            executable: acc.executable,
-            rent_epoch: acc.rent_epoch,
-        });
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let (pk, acc) = account_info(&state, &req)?;
+    let resp = acc
+        .map(|acc| AccountResp::encoded(&render, req.encoding.as_deref(), &pk, &acc))
+        .transpose()?;
 
     // metrics
     let elapsed = start.elapsed().as_secs_f64();
@@ -444,6 +1037,17 @@ async fn get_account_info(
     Ok(Json(resp))
 }
 
+/// Shared body of `getAccountInfo`.
+fn account_info(
+    state: &AppState,
+    req: &GetAccountInfoReq,
+) -> Result<(Pubkey, Option<Arc<StoredAccount>>), RpcError> {
+    let pk = Pubkey::try_from(req.pubkey.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid pubkey"))?;
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    Ok((pk, state.index.get_with_commitment(&pk, commitment)))
+}
+
 // ---------------------------------------------------------------------------
 // GET /getTokenAccountsByOwner (fast path)
 // ---------------------------------------------------------------------------
@@ -453,9 +1057,17 @@ struct GetTokenAccountsByOwnerReq {
     #[serde(default)]
     mint: Option<String>,
     #[serde(default)]
+    program_id: Option<String>,
+    #[serde(default)]
     limit: Option<usize>,
     #[serde(default)]
     offset: Option<usize>,
+    #[serde(default)]
+    commitment: Option<String>,
+    #[serde(default)]
+    encoding: Option<String>, // base58 / base64 (default) / base64+zstd / jsonParsed
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
 }
 
 async fn get_token_accounts_by_owner(
@@ -466,70 +1078,175 @@ async fn get_token_accounts_by_owner(
     check_api_key(&state, &headers)?;
     let start = Instant::now();
 
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let encoding = req.encoding.clone();
+    let out = token_accounts_by_owner(&state, req)?
+        .iter()
+        .map(|(k, acc)| AccountResp::encoded(&render, encoding.as_deref(), k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // metrics
+    let elapsed = start.elapsed().as_secs_f64();
+    REQUEST_DURATION
+        .with_label_values(&["getTokenAccountsByOwner"])
+        .observe(elapsed);
+    REQUEST_COUNT
+        .with_label_values(&["getTokenAccountsByOwner", "200"])
+        .inc();
+
+    Ok(Json(out))
+}
+
+/// Shared body of `getTokenAccountsByOwner`.
+fn token_accounts_by_owner(
+    state: &AppState,
+    req: GetTokenAccountsByOwnerReq,
+) -> Result<Vec<(Pubkey, Arc<StoredAccount>)>, RpcError> {
     let owner_pk = Pubkey::try_from(req.owner.as_str())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid owner pubkey".into()))?;
-
-    // Fast‑path: the owner index already gives us all accounts owned by this
-    // address (including SPL‑Token accounts). We just filter by mint if requested.
-    let mut accounts: Vec<(Pubkey, Arc<solana_sdk::account::Account>)> = {
-        if let Some(owner_vec) = state.index.owner_index.get(&owner_pk) {
-            let vec = owner_vec.read().unwrap();
-            vec.iter()
-                .filter_map(|pk| state.index.get(pk).map(|acc| (*pk, acc)))
-                .collect()
-        } else {
-            Vec::new()
+        .map_err(|_| RpcError::invalid_params("invalid owner pubkey"))?;
+
+    // Fast‑path: the wallet index already gives us every token account held
+    // by this address; with a mint, the shorter of its and the wallet's
+    // lists is walked instead.
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let accounts = match req.mint.as_deref() {
+        Some(mint) => {
+            let mint_pk =
+                Pubkey::try_from(mint).map_err(|_| RpcError::invalid_params("invalid mint"))?;
+            state
+                .index
+                .get_token_accounts_by_owner_and_mint(&owner_pk, &mint_pk, commitment)
         }
+        None => state.index.get_token_accounts_by_owner(&owner_pk, commitment),
     };
+    filter_token_accounts(accounts, None, req.program_id, req.offset, req.limit)
+}
 
+/// Mint / program filters and pagination shared by the token‑account
+/// look‑ups.
+fn filter_token_accounts(
+    mut accounts: Vec<(Pubkey, Arc<StoredAccount>)>,
+    mint: Option<String>,
+    program_id: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Vec<(Pubkey, Arc<StoredAccount>)>, RpcError> {
     // Optional mint filter – token accounts have the mint in the first 32 bytes.
-    if let Some(mint_str) = req.mint {
+    if let Some(mint_str) = mint {
         let mint_pk = Pubkey::try_from(mint_str.as_str())
-            .map_err(|_| (StatusCode::BAD_REQUEST, "invalid mint".into()))?;
+            .map_err(|_| RpcError::invalid_params("invalid mint"))?;
         accounts.retain(|(_, acc)| {
             acc.data.len() >= 32 && &acc.data[0..32] == mint_pk.as_ref()
         });
     }
 
+    // Optional program filter – SPL Token or Token‑2022.
+    if let Some(program_str) = program_id {
+        let program_pk = Pubkey::try_from(program_str.as_str())
+            .map_err(|_| RpcError::invalid_params("invalid program id"))?;
+        if !token::is_token_program(&program_pk) {
+            return Err(RpcError::invalid_params(
+                "Invalid param: unrecognized Token program id",
+            ));
+        }
+        accounts.retain(|(_, acc)| acc.owner == program_pk);
+    }
+
     // Pagination
-    if let Some(offset) = req.offset {
+    if let Some(offset) = offset {
         if offset < accounts.len() {
             accounts.drain(0..offset);
         } else {
             accounts.clear();
         }
     }
-    if let Some(limit) = req.limit {
+    if let Some(limit) = limit {
         accounts.truncate(limit);
     }
 
-    let out: Vec<AccountResp> = accounts
-        .into_iter()
-        .map(|(k, acc)| AccountResp {
-            pubkey: k.to_string(),
-            lamports: acc.lamports,
-            data: base64::encode(&acc.data),
-            owner: acc.owner.to_string(),
-            executable: acc.executable,
-            rent_epoch: acc.rent_epoch,
-        })
-        .collect();
+    Ok(accounts)
+}
+
+// ---------------------------------------------------------------------------
+// GET /getTokenAccountsByDelegate (fast path)
+// ---------------------------------------------------------------------------
+#[derive(Deserialize)]
+struct GetTokenAccountsByDelegateReq {
+    delegate: String,
+    #[serde(default)]
+    mint: Option<String>,
+    #[serde(default)]
+    program_id: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: Option<usize>,
+    #[serde(default)]
+    commitment: Option<String>,
+    #[serde(default)]
+    encoding: Option<String>, // base58 / base64 (default) / base64+zstd / jsonParsed
+    #[serde(default)]
+    data_slice: Option<UiDataSliceConfig>,
+}
+
+async fn get_token_accounts_by_delegate(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<GetTokenAccountsByDelegateReq>,
+) -> Result<Json<Vec<AccountResp>>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let start = Instant::now();
+
+    let render = Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    let encoding = req.encoding.clone();
+    let out = token_accounts_by_delegate(&state, req)?
+        .iter()
+        .map(|(k, acc)| AccountResp::encoded(&render, encoding.as_deref(), k, acc))
+        .collect::<Result<Vec<_>, _>>()?;
 
     // metrics
     let elapsed = start.elapsed().as_secs_f64();
     REQUEST_DURATION
-        .with_label_values(&["getTokenAccountsByOwner"])
+        .with_label_values(&["getTokenAccountsByDelegate"])
         .observe(elapsed);
     REQUEST_COUNT
-        .with_label_values(&["getTokenAccountsByOwner", "200"])
+        .with_label_values(&["getTokenAccountsByDelegate", "200"])
         .inc();
 
     Ok(Json(out))
 }
 
+/// Shared body of `getTokenAccountsByDelegate`.
+fn token_accounts_by_delegate(
+    state: &AppState,
+    req: GetTokenAccountsByDelegateReq,
+) -> Result<Vec<(Pubkey, Arc<StoredAccount>)>, RpcError> {
+    let delegate_pk = Pubkey::try_from(req.delegate.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid delegate pubkey"))?;
+
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let accounts = state
+        .index
+        .get_token_accounts_by_delegate(&delegate_pk, commitment);
+    filter_token_accounts(accounts, req.mint, req.program_id, req.offset, req.limit)
+}
+
 // ---------------------------------------------------------------------------
 // GET /getLargestTokenAccounts (fast path)
 // ---------------------------------------------------------------------------
+/// Most holders `getLargestTokenAccounts` returns, as in Solana.
+const MAX_LARGEST_TOKEN_ACCOUNTS: usize = 20;
+
 #[derive(Deserialize)]
 struct GetLargestTokenAccountsReq {
     mint: String,
@@ -541,42 +1258,200 @@ async fn get_largest_token_accounts(
     Extension(state): Extension<AppState>,
     headers: HeaderMap,
     Json(req): Json<GetLargestTokenAccountsReq>,
-) -> Result<Json<Vec<AccountResp>>, (StatusCode, String)> {
+) -> Result<Json<Vec<TokenAccountBalance>>, (StatusCode, String)> {
     check_api_key(&state, &headers)?;
     let start = Instant::now();
 
+    let out = largest_token_accounts(&state, &req)?;
+
+    // metrics
+    let elapsed = start.elapsed().as_secs_f64();
+    REQUEST_DURATION
+        .with_label_values(&["getLargestTokenAccounts"])
+        .observe(elapsed);
+    REQUEST_COUNT
+        .with_label_values(&["getLargestTokenAccounts", "200"])
+        .inc();
+
+    Ok(Json(out))
+}
+
+/// Shared body of `getLargestTokenAccounts`: the largest holders of a mint by
+/// token amount, rendered with the decimals of the cached mint account.
+fn largest_token_accounts(
+    state: &AppState,
+    req: &GetLargestTokenAccountsReq,
+) -> Result<Vec<TokenAccountBalance>, RpcError> {
     let mint_pk = Pubkey::try_from(req.mint.as_str())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid mint".into()))?;
-    let limit = req.limit.unwrap_or(10);
+        .map_err(|_| RpcError::invalid_params("invalid mint"))?;
+    let limit = req.limit.unwrap_or(MAX_LARGEST_TOKEN_ACCOUNTS);
+    if limit > MAX_LARGEST_TOKEN_ACCOUNTS {
+        return Err(RpcError::invalid_params(format!(
+            "limit must be at most {MAX_LARGEST_TOKEN_ACCOUNTS}"
+        )));
+    }
+    // The ranking follows rooted state, so the mint is read at finalized too.
+    let decimals = mint(state, &mint_pk, Commitment::Finalized)?.decimals;
 
-    let accounts = state
+    Ok(state
         .index
-        .get_largest_token_accounts(&mint_pk, limit);
-
-    let out: Vec<AccountResp> = accounts
+        .get_largest_token_accounts(&mint_pk, limit)
         .into_iter()
-        .map(|(k, acc)| AccountResp {
-            pubkey: k.to_string(),
-            lamports: acc.lamports,
-            data: base64::encode(&acc.data),
-            owner: acc.owner.to_string(),
-            executable: acc.executable,
-            rent_epoch: acc.rent_epoch,
+        .map(|(key, amount)| TokenAccountBalance {
+            address: key.to_string(),
+            amount: UiTokenAmount::new(amount, decimals),
         })
-        .collect();
+        .collect())
+}
+
+/// The cached mint account `mint_pk` at `commitment`, decoded.
+fn mint(
+    state: &AppState,
+    mint_pk: &Pubkey,
+    commitment: Commitment,
+) -> Result<token::Mint, RpcError> {
+    state
+        .index
+        .get_with_commitment(mint_pk, commitment)
+        .and_then(|acc| token::Mint::unpack(&acc.owner, &acc.data))
+        .ok_or_else(|| RpcError::invalid_params("Invalid param: could not find mint"))
+}
+
+// ---------------------------------------------------------------------------
+// GET /getTokenAccountBalance
+// ---------------------------------------------------------------------------
+#[derive(Deserialize)]
+struct GetTokenAccountBalanceReq {
+    pubkey: String,
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+async fn get_token_account_balance(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<GetTokenAccountBalanceReq>,
+) -> Result<Json<UiTokenAmount>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let start = Instant::now();
+
+    let out = token_account_balance(&state, &req)?;
 
     // metrics
     let elapsed = start.elapsed().as_secs_f64();
     REQUEST_DURATION
-        .with_label_values(&["getLargestTokenAccounts"])
+        .with_label_values(&["getTokenAccountBalance"])
         .observe(elapsed);
     REQUEST_COUNT
-        .with_label_values(&["getLargestTokenAccounts", "200"])
+        .with_label_values(&["getTokenAccountBalance", "200"])
+        .inc();
+
+    Ok(Json(out))
+}
+
+/// Shared body of `getTokenAccountBalance`: the account's amount with the
+/// decimals of its mint.
+fn token_account_balance(
+    state: &AppState,
+    req: &GetTokenAccountBalanceReq,
+) -> Result<UiTokenAmount, RpcError> {
+    let pk = Pubkey::try_from(req.pubkey.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid pubkey"))?;
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let acc = state
+        .index
+        .get_with_commitment(&pk, commitment)
+        .ok_or_else(|| RpcError::invalid_params("Invalid param: could not find account"))?;
+    let token_account = token::TokenAccount::unpack(&acc.owner, &acc.data)
+        .ok_or_else(|| RpcError::invalid_params("Invalid param: not a Token account"))?;
+    let decimals = mint(state, &token_account.mint, commitment)?.decimals;
+    Ok(UiTokenAmount::new(token_account.amount, decimals))
+}
+
+// ---------------------------------------------------------------------------
+// GET /getTokenSupply
+// ---------------------------------------------------------------------------
+#[derive(Deserialize)]
+struct GetTokenSupplyReq {
+    mint: String,
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+async fn get_token_supply(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<GetTokenSupplyReq>,
+) -> Result<Json<UiTokenAmount>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let start = Instant::now();
+
+    let out = token_supply(&state, &req)?;
+
+    // metrics
+    let elapsed = start.elapsed().as_secs_f64();
+    REQUEST_DURATION
+        .with_label_values(&["getTokenSupply"])
+        .observe(elapsed);
+    REQUEST_COUNT
+        .with_label_values(&["getTokenSupply", "200"])
         .inc();
 
     Ok(Json(out))
 }
 
+/// Shared body of `getTokenSupply`.
+fn token_supply(state: &AppState, req: &GetTokenSupplyReq) -> Result<UiTokenAmount, RpcError> {
+    let mint_pk = Pubkey::try_from(req.mint.as_str())
+        .map_err(|_| RpcError::invalid_params("invalid mint"))?;
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    let mint = mint(state, &mint_pk, commitment)?;
+    Ok(UiTokenAmount::new(mint.supply, mint.decimals))
+}
+
+// ---------------------------------------------------------------------------
+// GET /getSlot
+// ---------------------------------------------------------------------------
+#[derive(Deserialize, Default)]
+struct GetSlotReq {
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+/// The body is optional: a bare `GET` or `POST` reads the finalized slot.
+async fn get_slot(
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<u64>, (StatusCode, String)> {
+    check_api_key(&state, &headers)?;
+    let start = Instant::now();
+    let req: GetSlotReq = if body.is_empty() {
+        GetSlotReq::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    };
+
+    let slot = slot(&state, &req)?;
+
+    // metrics
+    let elapsed = start.elapsed().as_secs_f64();
+    REQUEST_DURATION
+        .with_label_values(&["getSlot"])
+        .observe(elapsed);
+    REQUEST_COUNT
+        .with_label_values(&["getSlot", "200"])
+        .inc();
+
+    Ok(Json(slot))
+}
+
+/// Shared body of `getSlot`.
+fn slot(state: &AppState, req: &GetSlotReq) -> Result<u64, RpcError> {
+    let commitment = parse_commitment(req.commitment.as_deref())?;
+    Ok(state.index.slot_with_commitment(commitment))
+}
+
 // ---------------------------------------------------------------------------
 // POST /simulateTransaction (proxy mode)
 // ---------------------------------------------------------------------------
@@ -616,7 +1491,7 @@ async fn websocket_handler(
     Query(params): Query<WsQuery>,
 ) -> impl IntoResponse {
     // `params` can contain optional `program` or `owner` filters.
-    ws.on_upgrade(move |socket| handle_socket(socket, state.txs.clone(), params))
+    ws.on_upgrade(move |socket| handle_socket(socket, state, params))
 }
 
 #[derive(Deserialize, Default)]
@@ -625,13 +1500,9 @@ struct WsQuery {
     owner: Option<String>,
 }
 
-async fn handle_socket(
-    socket: WebSocket,
-    txs: Arc<broadcast::Sender<WsEvent>>,
-    filter: WsQuery,
-) {
+async fn handle_socket(socket: WebSocket, state: AppState, filter: WsQuery) {
     let (mut sender, _receiver) = socket.split();
-    let mut rx = txs.subscribe();
+    let mut rx = state.txs.subscribe();
 
     // Pre‑parse filter pubkeys (if any) for fast comparison.
     let program_filter = filter
@@ -650,12 +1521,7 @@ async fn handle_socket(
                 if let Some(p) = program_filter {
                     // We need the account to check its owner – cheap because we have it cached.
                     let pk = Pubkey::try_from(pubkey.as_str()).unwrap();
-                    if let Some(acc) = txs
-                        .clone()
-                        .into_inner()
-                        .index
-                        .get(&pk)
-                    {
+                    if let Some(acc) = state.index.get(&pk) {
                         acc.owner == p
                     } else {
                         false
@@ -687,3 +1553,444 @@ async fn handle_socket(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashSet};
+
+    #[tokio::test]
+    async fn slot_notifier_reports_every_advance() {
+        let index = Arc::new(ShardedIndex::default());
+        let state = AppState::for_tests(index.clone());
+        let mut rx = state.txs.subscribe();
+        slot_notifier(&index.slots(), state.txs.clone());
+
+        index.process_slot(5, Some(4));
+        index.process_slot(6, Some(5));
+        index.process_slot(6, Some(5));
+        index.root_slot(5);
+
+        let mut events = Vec::new();
+        while let Ok(WsEvent::SlotUpdated { slot, commitment }) = rx.try_recv() {
+            events.push((slot, commitment));
+        }
+        assert_eq!(
+            events,
+            [
+                (5, "processed"),
+                (6, "processed"),
+                (5, "confirmed"),
+                (5, "finalized"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_slot_body_is_optional() {
+        let index = Arc::new(ShardedIndex::default());
+        index.process_slot(7, Some(6));
+        index.root_slot(3);
+        let state = AppState::for_tests(index);
+        let call = |body: &'static str| {
+            get_slot(
+                Extension(state.clone()),
+                HeaderMap::new(),
+                Bytes::from_static(body.as_bytes()),
+            )
+        };
+
+        assert_eq!(call("").await.unwrap().0, 3);
+        assert_eq!(call(r#"{"commitment":"processed"}"#).await.unwrap().0, 7);
+        assert_eq!(call("{").await.unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn audit_reports_index_state() {
+        let index = Arc::new(ShardedIndex::default());
+        let key = Pubkey::new_unique();
+        let account = solana_sdk::account::Account {
+            lamports: 1,
+            owner: Pubkey::new_unique(),
+            ..Default::default()
+        };
+        index.insert(key, StoredAccount::new(account, 1, 0));
+        index.finish_startup();
+        let state = AppState::for_tests(index);
+
+        let Json(report) = audit_handler(Extension(state), HeaderMap::new())
+            .await
+            .unwrap();
+        assert!(report.consistent);
+        assert_eq!(report.accounts, 1);
+
+        assert!(report.missing_sample.is_empty() && report.stale_sample.is_empty());
+
+        let entry = AuditEntry::new(&IndexEntry::Memcmp {
+            program: key,
+            offset: 8,
+            value: Box::new([0, 1]),
+            key,
+        });
+        assert_eq!(
+            (entry.index, entry.detail.as_deref()),
+            ("memcmp", Some("8:12"))
+        );
+    }
+
+    /// A `mint` with 0 decimals and `holders` token accounts holding 1..=n.
+    pub(crate) fn token_holders(index: &ShardedIndex, mint: Pubkey, holders: u64) {
+        let mut data = vec![0u8; token::MINT_LEN];
+        data[45] = 1;
+        let account = |data| solana_sdk::account::Account {
+            lamports: 1,
+            data,
+            owner: token::TOKEN_PROGRAM_ID,
+            ..Default::default()
+        };
+        index.insert(mint, StoredAccount::new(account(data), 1, 0));
+        for amount in 1..=holders {
+            let mut data = vec![0u8; token::ACCOUNT_LEN];
+            data[0..32].copy_from_slice(mint.as_ref());
+            data[64..72].copy_from_slice(&amount.to_le_bytes());
+            data[108] = 1;
+            index.insert(Pubkey::new_unique(), StoredAccount::new(account(data), 1, 0));
+        }
+    }
+
+    #[test]
+    fn largest_token_accounts_limit_is_capped() {
+        let index = Arc::new(ShardedIndex::default());
+        let mint = Pubkey::new_unique();
+        token_holders(&index, mint, 25);
+        index.finish_startup();
+        let state = AppState::for_tests(index);
+        let largest = |limit| {
+            largest_token_accounts(
+                &state,
+                &GetLargestTokenAccountsReq {
+                    mint: mint.to_string(),
+                    limit,
+                },
+            )
+        };
+
+        let top = largest(None).unwrap();
+        assert_eq!(top.len(), MAX_LARGEST_TOKEN_ACCOUNTS);
+        assert_eq!(top[0].amount.amount, "25");
+        assert_eq!(largest(Some(3)).unwrap().len(), 3);
+        assert_eq!(largest(Some(20)).unwrap().len(), 20);
+        assert!(matches!(
+            largest(Some(21)),
+            Err(e) if e.code == jsonrpc::INVALID_PARAMS
+        ));
+    }
+
+    #[tokio::test]
+    async fn token_largest_accounts_alias_shares_the_cap() {
+        let index = Arc::new(ShardedIndex::default());
+        let mint = Pubkey::new_unique();
+        token_holders(&index, mint, 25);
+        index.finish_startup();
+        let state = AppState::for_tests(index);
+        let route = |limit| {
+            let req = format!(r#"{{"mint":"{mint}","limit":{limit}}}"#);
+            let req = serde_json::from_str(&req).unwrap();
+            get_largest_token_accounts(Extension(state.clone()), HeaderMap::new(), Json(req))
+        };
+
+        let Json(top) = route(20).await.unwrap();
+        assert_eq!(top.len(), 20);
+        let (status, _) = route(21).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn token_accounts_routes_honor_encoding() {
+        let index = Arc::new(ShardedIndex::default());
+        let mint = Pubkey::new_unique();
+        token_holders(&index, mint, 2);
+        index.finish_startup();
+        let state = AppState::for_tests(index);
+        // `token_holders` leaves the wallet field zeroed.
+        let wallet = Pubkey::default();
+        let route = |encoding: &str| {
+            let req = format!(r#"{{"owner":"{wallet}","mint":"{mint}"{encoding}}}"#);
+            let req = serde_json::from_str(&req).unwrap();
+            get_token_accounts_by_owner(Extension(state.clone()), HeaderMap::new(), Json(req))
+        };
+
+        let Json(plain) = route("").await.unwrap();
+        let plain = serde_json::to_value(plain).unwrap();
+        assert_eq!(plain.as_array().unwrap().len(), 2);
+        assert!(plain[0]["data"].is_string());
+
+        let Json(parsed) = route(r#","encoding":"jsonParsed""#).await.unwrap();
+        let parsed = serde_json::to_value(parsed).unwrap();
+        assert_eq!(parsed[0]["data"]["program"], "spl-token");
+        assert_eq!(
+            parsed[0]["data"]["parsed"]["info"]["mint"],
+            mint.to_string()
+        );
+
+        let (status, _) = route(r#","encoding":"jsonParsed","data_slice":{"offset":0,"length":4}"#)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    fn owned(owner: Pubkey, lamports: u64, slot: u64) -> StoredAccount {
+        let account = solana_sdk::account::Account {
+            lamports,
+            owner,
+            ..Default::default()
+        };
+        StoredAccount::new(account, slot, 0)
+    }
+
+    /// Walk `req` page by page; the keys of each page.
+    fn walk_pages(state: &AppState, req: serde_json::Value) -> Result<Vec<Vec<Pubkey>>, RpcError> {
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut req = req.clone();
+            req["cursor"] = serde_json::json!(cursor);
+            let page = program_accounts_page(state, serde_json::from_value(req).unwrap())?;
+            pages.push(page.accounts.into_iter().map(|(key, _)| key).collect());
+            cursor = page.cursor;
+            if cursor.is_none() {
+                return Ok(pages);
+            }
+        }
+    }
+
+    #[test]
+    fn program_accounts_page_walks_every_account_once() {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        let mut keys: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        keys.sort();
+        for key in &keys {
+            index.insert(*key, owned(program, 1, 1));
+        }
+        index.insert(Pubkey::new_unique(), owned(Pubkey::new_unique(), 1, 1));
+        index.finish_startup();
+        index.root_slot(1);
+        let state = AppState::for_tests(index);
+
+        let req = |limit| serde_json::json!({"program": program.to_string(), "limit": limit});
+        let pages = walk_pages(&state, req(3)).unwrap();
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [3, 3, 1]);
+        assert_eq!(pages.concat(), keys);
+        // A full last page still hands out a cursor; the next page is empty.
+        let pages = walk_pages(&state, req(7)).unwrap();
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [7, 0]);
+    }
+
+    #[test]
+    fn program_accounts_page_overlays_pending_writes() {
+        let index = Arc::new(ShardedIndex::default());
+        let (program, elsewhere) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut rooted: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        rooted.sort();
+        for key in &rooted {
+            index.insert(*key, owned(program, 1, 1));
+        }
+        index.finish_startup();
+        index.root_slot(1);
+        // Slot 2 closes one account, moves another away and creates one.
+        let created = Pubkey::new_unique();
+        index.process_slot(2, Some(1));
+        index.insert_processed(rooted[0], owned(program, 0, 2));
+        index.insert_processed(rooted[1], owned(elsewhere, 1, 2));
+        index.insert_processed(created, owned(program, 1, 2));
+        index.insert_processed(Pubkey::new_unique(), owned(elsewhere, 1, 2));
+        let state = AppState::for_tests(index);
+
+        let walk = |commitment| {
+            let req = serde_json::json!({
+                "program": program.to_string(),
+                "limit": 1,
+                "commitment": commitment,
+            });
+            let pages = walk_pages(&state, req).unwrap();
+            assert!(pages.iter().all(|page| page.len() <= 1));
+            pages.concat()
+        };
+        let mut expected = vec![rooted[2], rooted[3], created];
+        expected.sort();
+        assert_eq!(walk("processed"), expected);
+        assert_eq!(walk("finalized"), rooted);
+    }
+
+    #[test]
+    fn program_accounts_page_walk_survives_writes_between_pages() {
+        let index = Arc::new(ShardedIndex::default());
+        let (program, elsewhere) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // Hashed keys spread over the whole key space, on both sides of
+        // every cursor.
+        let mut n = 0u64;
+        let mut fresh = || {
+            n += 1;
+            Pubkey::new_from_array(hashv(&[b"walk", &n.to_le_bytes()]).to_bytes())
+        };
+        let initial: Vec<Pubkey> = (0..300).map(|_| fresh()).collect();
+        let outside: Vec<Pubkey> = (0..50).map(|_| fresh()).collect();
+        for key in &initial {
+            index.insert(*key, owned(program, 1, 1));
+        }
+        for key in &outside {
+            index.insert(*key, owned(elsewhere, 1, 1));
+        }
+        index.finish_startup();
+        index.root_slot(1);
+        let state = AppState::for_tests(index.clone());
+
+        // Keys closed or moved away at some point of the walk may be missed;
+        // every other initial key must be returned.
+        let mut gone = HashSet::new();
+        let mut ever: HashSet<Pubkey> = initial.iter().copied().collect();
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        let mut slot = 1;
+        loop {
+            let req = serde_json::json!({
+                "program": program.to_string(),
+                "limit": 16,
+                "commitment": "processed",
+                "cursor": cursor,
+            });
+            let page = program_accounts_page(&state, serde_json::from_value(req).unwrap()).unwrap();
+            seen.extend(page.accounts.iter().map(|(key, _)| *key));
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+
+            slot += 1;
+            let i = slot as usize;
+            index.process_slot(slot, Some(slot - 1));
+            let created = fresh();
+            index.insert_processed(created, owned(program, 1, slot));
+            ever.insert(created);
+            let closed = initial[i * 7 % initial.len()];
+            index.insert_processed(closed, owned(program, 0, slot));
+            gone.insert(closed);
+            let moved_out = initial[(i * 13 + 5) % initial.len()];
+            index.insert_processed(moved_out, owned(elsewhere, 1, slot));
+            gone.insert(moved_out);
+            let moved_in = outside[i % outside.len()];
+            index.insert_processed(moved_in, owned(program, 1, slot));
+            ever.insert(moved_in);
+            let updated = initial[(i * 3 + 1) % initial.len()];
+            index.insert_processed(updated, owned(program, 2, slot));
+            // Part of the writes reach the shards mid-walk.
+            if slot % 3 == 0 {
+                index.root_slot(slot - 1);
+            }
+        }
+
+        assert!(
+            seen.windows(2).all(|w| w[0] < w[1]),
+            "keys out of order or repeated"
+        );
+        let seen: HashSet<Pubkey> = seen.into_iter().collect();
+        for key in initial.iter().filter(|key| !gone.contains(*key)) {
+            assert!(seen.contains(key), "skipped {key}");
+        }
+        assert!(seen.is_subset(&ever));
+        assert!(gone.len() > 20);
+    }
+
+    #[test]
+    fn program_accounts_page_rejects_foreign_cursors() {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        for _ in 0..3 {
+            index.insert(Pubkey::new_unique(), owned(program, 1, 1));
+        }
+        index.finish_startup();
+        index.root_slot(1);
+        let state = AppState::for_tests(index);
+
+        let page = |req: serde_json::Value| {
+            program_accounts_page(&state, serde_json::from_value(req).unwrap())
+        };
+        let first = serde_json::json!({"program": program.to_string(), "limit": 1});
+        let cursor = page(first.clone()).unwrap().cursor.unwrap();
+        let mut next = first.clone();
+        next["cursor"] = serde_json::json!(cursor);
+        assert!(page(next.clone()).is_ok());
+
+        let invalid = |req: serde_json::Value| {
+            matches!(page(req), Err(e) if e.code == jsonrpc::INVALID_PARAMS)
+        };
+        let mut garbage = first.clone();
+        garbage["cursor"] = serde_json::json!("not a cursor");
+        assert!(invalid(garbage));
+        let mut other_program = next.clone();
+        other_program["program"] = serde_json::json!(Pubkey::new_unique().to_string());
+        assert!(invalid(other_program));
+        let mut other_filters = next.clone();
+        other_filters["filters"] = serde_json::json!([{"dataSize": 0}]);
+        assert!(invalid(other_filters));
+        let mut other_commitment = next;
+        other_commitment["commitment"] = serde_json::json!("processed");
+        assert!(invalid(other_commitment));
+    }
+
+    #[tokio::test]
+    async fn program_accounts_page_waits_for_the_snapshot() {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        for _ in 0..3 {
+            index.insert(Pubkey::new_unique(), owned(program, 1, 1));
+        }
+        let state = AppState::for_tests(index.clone());
+        let route = || {
+            let req = serde_json::json!({"program": program.to_string()});
+            let req = serde_json::from_value(req).unwrap();
+            get_program_accounts_page(Extension(state.clone()), HeaderMap::new(), Json(req))
+        };
+
+        // An empty last page would tell the client the program has no
+        // accounts.
+        let (status, _) = route().await.err().unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        index.finish_startup();
+        index.root_slot(1);
+        let Json(page) = route().await.unwrap();
+        assert_eq!(page.accounts.len(), 3);
+        assert!(page.cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn streamed_program_accounts_wait_for_the_snapshot() {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        for _ in 0..3 {
+            index.insert(Pubkey::new_unique(), owned(program, 1, 1));
+        }
+        let state = AppState::for_tests(index);
+        let route = |stream: Option<&str>| {
+            let req = serde_json::json!({"program": program.to_string(), "stream": stream});
+            let req = serde_json::from_value(req).unwrap();
+            get_program_accounts(Extension(state.clone()), HeaderMap::new(), Json(req))
+        };
+
+        // Unstreamed, the request scans the shards and finds every account.
+        let resp = route(None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let accounts: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(accounts.len(), 3);
+        // Streamed, it would walk the empty indexes and send `[]`.
+        for format in ["json", "ndjson"] {
+            let (status, _) = route(Some(format)).await.err().unwrap();
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{format}");
+        }
+    }
+}
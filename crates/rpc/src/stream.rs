@@ -0,0 +1,442 @@
+//! Streamed program‑account responses: `getProgramAccounts` with
+//! `"stream": "json"` or `"ndjson"`, and the binary dump of
+//! `getProgramAccountsBinary` ([`fractal_rle::dump`] format).
+//!
+//! A blocking task walks the program in key order one batch at a time (the
+//! walk behind `getProgramAccountsPage`), encodes each batch and hands the
+//! bytes to the response body through a small bounded channel. Memory stays
+//! at a few batches whatever the size of the program; a client that reads
+//! slowly fills the channel and stalls the walk, and one that disconnects
+//! drops the body, which ends the walk before its next batch. Pending writes
+//! are snapshotted once, when the walk starts.
+//!
+//! The status line is sent before the walk starts, so an account that cannot
+//! be encoded (base58 data over 128 bytes) aborts the body instead of
+//! producing an error response.
+
+use {
+    super::{
+        filter::{self, AccountFilter, Filter},
+        indexed_memcmp, parse_commitment, AccountResp, AppState, GetProgramAccountsReq, Render,
+        RpcError, PROGRAM_ACCOUNTS_PLAN, REQUEST_DURATION,
+    },
+    axum::{
+        body::{Body, Bytes},
+        http::header,
+        response::{IntoResponse, Response},
+    },
+    fractal_rle::dump::{self, DumpWriter},
+    fractal_shard::{Commitment, StoredAccount},
+    futures::stream,
+    serde::Deserialize,
+    solana_sdk::pubkey::Pubkey,
+    std::{io, sync::Arc, time::Instant},
+    tokio::sync::mpsc,
+};
+
+/// Accounts encoded per chunk.
+const BATCH: usize = 1_000;
+/// Chunks buffered between the walk and the client socket.
+const CHUNKS_IN_FLIGHT: usize = 4;
+/// Uncompressed bytes after which a dump frame is cut, so a batch of large
+/// accounts still compresses in bounded pieces.
+const DUMP_FRAME_BYTES: usize = 1 << 20;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    /// One JSON array, as without streaming.
+    Json,
+    /// One account object per line.
+    Ndjson,
+}
+
+impl StreamFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// What a streamed response walks, parsed and validated up front.
+struct Walk {
+    program: Pubkey,
+    filters: Vec<AccountFilter>,
+    commitment: Commitment,
+    limit: Option<usize>,
+}
+
+impl Walk {
+    fn new(
+        program: &str,
+        filters: Option<Vec<Filter>>,
+        commitment: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Self, RpcError> {
+        Ok(Self {
+            program: Pubkey::try_from(program)
+                .map_err(|_| RpcError::invalid_params("invalid program pubkey"))?,
+            filters: filter::verify(filters.unwrap_or_default())?,
+            commitment: parse_commitment(commitment)?,
+            limit,
+        })
+    }
+
+    /// Run the walk on a blocking task and return the body it feeds: `open`,
+    /// what `encode` makes of each batch, then `close`. `handler` labels the
+    /// latency metric.
+    fn spawn<E>(
+        self,
+        state: AppState,
+        handler: &'static str,
+        open: &'static [u8],
+        close: &'static [u8],
+        encode: E,
+    ) -> Body
+    where
+        E: FnMut(&AppState, &[(Pubkey, Arc<StoredAccount>)]) -> Result<Vec<u8>, RpcError>
+            + Send
+            + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(CHUNKS_IN_FLIGHT);
+        tokio::task::spawn_blocking(move || {
+            let start = Instant::now();
+            if self.run(&state, &tx, open, close, encode).is_ok() {
+                REQUEST_DURATION
+                    .with_label_values(&[handler])
+                    .observe(start.elapsed().as_secs_f64());
+            }
+        });
+        Body::from_stream(stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| (chunk, rx))
+        }))
+    }
+
+    /// `Err` if the client went away, or the body was aborted because an
+    /// account could not be encoded or the startup snapshot is not indexed
+    /// yet. `close` is only sent after a walk over every rooted account.
+    fn run<E>(
+        &self,
+        state: &AppState,
+        tx: &mpsc::Sender<io::Result<Bytes>>,
+        open: &'static [u8],
+        close: &'static [u8],
+        mut encode: E,
+    ) -> Result<(), ()>
+    where
+        E: FnMut(&AppState, &[(Pubkey, Arc<StoredAccount>)]) -> Result<Vec<u8>, RpcError>,
+    {
+        // A send only fails once the client is gone.
+        let send = |chunk: io::Result<Bytes>| tx.blocking_send(chunk).map_err(|_| ());
+        // Callers refuse the request first; this keeps a walk of the empty
+        // indexes from ever ending in what reads as a complete response.
+        if !state.index.is_ready() {
+            send(Err(io::Error::other(RpcError::node_unhealthy().message)))?;
+            return Err(());
+        }
+        let memcmp = indexed_memcmp(state, &self.program, &self.filters);
+        let plan = if memcmp.is_some() { "index" } else { "scan" };
+        PROGRAM_ACCOUNTS_PLAN.with_label_values(&[plan]).inc();
+
+        if !open.is_empty() {
+            send(Ok(Bytes::from_static(open)))?;
+        }
+        let overlay = state.index.pending_overlay(&self.program, self.commitment);
+        let mut remaining = self.limit.unwrap_or(usize::MAX);
+        let mut after = None;
+        while remaining > 0 {
+            // Batches that encode to nothing never send, so check directly.
+            if tx.is_closed() {
+                return Err(());
+            }
+            let batch = BATCH.min(remaining);
+            let accounts = state.index.get_program_accounts_page(
+                &self.program,
+                memcmp,
+                &overlay,
+                after.as_ref(),
+                batch,
+                |acc| self.filters.iter().all(|f| f.matches(&acc.data)),
+            );
+            match encode(state, &accounts) {
+                Ok(chunk) if chunk.is_empty() => {}
+                Ok(chunk) => send(Ok(chunk.into()))?,
+                Err(e) => {
+                    send(Err(io::Error::other(e.message)))?;
+                    return Err(());
+                }
+            }
+            if accounts.len() < batch {
+                break;
+            }
+            remaining -= batch;
+            after = accounts.last().map(|(key, _)| *key);
+        }
+        if !close.is_empty() {
+            send(Ok(Bytes::from_static(close)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Stream the accounts `req` selects in `format`. Request errors are
+/// reported before anything is sent, as is a startup snapshot still being
+/// indexed: the walk would come back empty where the unstreamed request
+/// scans.
+pub fn program_accounts(
+    state: AppState,
+    req: GetProgramAccountsReq,
+    format: StreamFormat,
+) -> Result<Response, RpcError> {
+    if req.offset.is_some() {
+        return Err(RpcError::invalid_params(
+            "offset is not supported when streaming; use getProgramAccountsPage",
+        ));
+    }
+    let walk = Walk::new(
+        &req.program,
+        req.filters,
+        req.commitment.as_deref(),
+        req.limit,
+    )?;
+    // Validate `encoding` / `data_slice` now; the walk builds a `Render` per
+    // batch since it cannot hold a borrow of the state across batches.
+    Render::new(
+        &state.index,
+        req.encoding.as_deref(),
+        req.data_slice,
+        req.commitment.as_deref(),
+    )?;
+    if !state.index.is_ready() {
+        return Err(RpcError::node_unhealthy());
+    }
+
+    let (open, close): (&[u8], &[u8]) = match format {
+        StreamFormat::Json => (b"[", b"]"),
+        StreamFormat::Ndjson => (b"", b""),
+    };
+    let mut first = true;
+    let body = walk.spawn(
+        state,
+        "getProgramAccounts",
+        open,
+        close,
+        move |state, accounts| {
+            let render = Render::new(
+                &state.index,
+                req.encoding.as_deref(),
+                req.data_slice,
+                req.commitment.as_deref(),
+            )?;
+            let mut chunk = Vec::new();
+            for (key, acc) in accounts {
+                let resp = AccountResp::encoded(&render, req.encoding.as_deref(), key, acc)?;
+                if format == StreamFormat::Json && !first {
+                    chunk.push(b',');
+                }
+                first = false;
+                serde_json::to_writer(&mut chunk, &resp).expect("serializing to a Vec");
+                if format == StreamFormat::Ndjson {
+                    chunk.push(b'\n');
+                }
+            }
+            Ok(chunk)
+        },
+    );
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct GetProgramAccountsBinaryReq {
+    program: String,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    filters: Option<Vec<Filter>>,
+    #[serde(default)]
+    commitment: Option<String>,
+}
+
+/// Stream the accounts `req` selects as a [`fractal_rle::dump`]. Request
+/// errors are reported before anything is sent, as is a startup snapshot
+/// still being indexed: the dump would hold only pending writes, yet end
+/// with [`dump::END`].
+pub fn program_accounts_binary(
+    state: AppState,
+    req: GetProgramAccountsBinaryReq,
+) -> Result<Response, RpcError> {
+    let walk = Walk::new(
+        &req.program,
+        req.filters,
+        req.commitment.as_deref(),
+        req.limit,
+    )?;
+    if !state.index.is_ready() {
+        return Err(RpcError::node_unhealthy());
+    }
+    let body = walk.spawn(
+        state,
+        "getProgramAccountsBinary",
+        dump::MAGIC,
+        dump::END,
+        |_, accounts| {
+            let mut writer = DumpWriter::default();
+            let mut chunk = Vec::new();
+            for (key, acc) in accounts {
+                writer.push(
+                    &key.to_bytes(),
+                    &acc.owner.to_bytes(),
+                    acc.lamports,
+                    acc.slot,
+                    &acc.data,
+                );
+                if writer.len() >= DUMP_FRAME_BYTES {
+                    writer.finish_frame(&mut chunk);
+                }
+            }
+            writer.finish_frame(&mut chunk);
+            Ok(chunk)
+        },
+    );
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], body).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        fractal_shard::ShardedIndex,
+        serde_json::{json, Value},
+    };
+
+    /// State with `n` accounts of a fresh program, rooted at slot 1.
+    fn state_with(n: usize) -> (AppState, Pubkey) {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        for _ in 0..n {
+            let account = solana_sdk::account::Account {
+                lamports: 1,
+                owner: program,
+                ..Default::default()
+            };
+            index.insert(Pubkey::new_unique(), StoredAccount::new(account, 1, 0));
+        }
+        index.finish_startup();
+        index.root_slot(1);
+        (AppState::for_tests(index), program)
+    }
+
+    async fn body(state: AppState, req: Value, format: StreamFormat) -> (String, Vec<u8>) {
+        let req = serde_json::from_value(req).unwrap();
+        let resp = program_accounts(state, req, format).ok().unwrap();
+        let content_type = resp.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (content_type, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn json_stream_is_one_array() {
+        let (state, program) = state_with(BATCH + 5);
+        let (content_type, bytes) = body(
+            state,
+            json!({"program": program.to_string()}),
+            StreamFormat::Json,
+        )
+        .await;
+        assert_eq!(content_type, "application/json");
+        let accounts: Vec<Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(accounts.len(), BATCH + 5);
+        assert_eq!(accounts[0]["owner"], json!(program.to_string()));
+
+        let (empty, _) = state_with(0);
+        let (_, bytes) = body(
+            empty,
+            json!({"program": program.to_string()}),
+            StreamFormat::Json,
+        )
+        .await;
+        assert_eq!(bytes, b"[]");
+    }
+
+    #[tokio::test]
+    async fn ndjson_stream_is_one_account_per_line() {
+        let (state, program) = state_with(BATCH + 5);
+        let (content_type, bytes) = body(
+            state,
+            json!({"program": program.to_string(), "limit": BATCH + 2}),
+            StreamFormat::Ndjson,
+        )
+        .await;
+        assert_eq!(content_type, "application/x-ndjson");
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.ends_with('\n'));
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // The limit cuts the walk in the middle of its second batch.
+        assert_eq!(lines.len(), BATCH + 2);
+        let mut keys: Vec<&str> = lines
+            .iter()
+            .map(|a| a["pubkey"].as_str().unwrap())
+            .collect();
+        keys.dedup();
+        assert_eq!(keys.len(), BATCH + 2);
+    }
+
+    #[test]
+    fn walk_stops_once_the_client_is_gone() {
+        let (state, program) = state_with(3 * BATCH);
+        let walk = Walk::new(&program.to_string(), None, None, None)
+            .ok()
+            .unwrap();
+        let (tx, rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+        drop(rx);
+
+        // Batches that encode to nothing would never hit a failed send.
+        let mut batches = 0;
+        let result = walk.run(&state, &tx, b"", b"", |_, _| {
+            batches += 1;
+            Ok(Vec::new())
+        });
+        assert!(result.is_err());
+        assert_eq!(batches, 0);
+    }
+
+    #[tokio::test]
+    async fn dump_waits_for_the_snapshot() {
+        let index = Arc::new(ShardedIndex::default());
+        let program = Pubkey::new_unique();
+        let state = AppState::for_tests(index.clone());
+        let req = || serde_json::from_value(json!({"program": program.to_string()})).unwrap();
+        let err = program_accounts_binary(state.clone(), req()).err().unwrap();
+        assert_eq!(err.code, crate::jsonrpc::NODE_UNHEALTHY);
+
+        // A walk started anyway aborts instead of closing the dump.
+        let walk = Walk::new(&program.to_string(), None, None, None)
+            .ok()
+            .unwrap();
+        let (tx, mut rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+        let run = tokio::task::spawn_blocking(move || {
+            walk.run(&state, &tx, dump::MAGIC, dump::END, |_, _| Ok(Vec::new()))
+        });
+        assert!(run.await.unwrap().is_err());
+        assert!(rx.recv().await.unwrap().is_err());
+        assert!(rx.recv().await.is_none());
+
+        index.finish_startup();
+        let state = AppState::for_tests(index);
+        let resp = program_accounts_binary(state, req()).ok().unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.as_ref(), [&dump::MAGIC[..], dump::END].concat());
+    }
+}